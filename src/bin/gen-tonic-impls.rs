@@ -0,0 +1,139 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+use gen_tonic_impls::Config;
+
+const USAGE: &str = "\
+Usage: gen-tonic-impls [OPTIONS] --out-dir <DIR> (--proto <FILE>... | --descriptor-set <FILE>)
+
+Options:
+    --proto <FILE>            .proto file to generate bindings for, may be repeated
+    -I, --include <DIR>       protoc include path, may be repeated
+    --descriptor-set <FILE>   encoded FileDescriptorSet to use instead of running protoc
+    --out-dir <DIR>           directory the generated files are written to
+    --client                  generate the client bindings
+    --server                  generate the server bindings
+    --tls                     generate TLS settings for client endpoints, needs tonic's
+                              `tls` feature
+    --check                   don't write anything, exit non-zero if the files in --out-dir
+                              differ from what would be generated
+    -h, --help                print this help
+
+If neither --client nor --server is given, both are generated.";
+
+#[derive(Debug, Default)]
+struct Args {
+    protos: Vec<PathBuf>,
+    includes: Vec<PathBuf>,
+    descriptor_set: Option<PathBuf>,
+    out_dir: Option<PathBuf>,
+    client: bool,
+    server: bool,
+    tls: bool,
+    check: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut parsed = Args::default();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().map(PathBuf::from).ok_or(format!("`{}` requires a value", name));
+        match arg.as_str() {
+            "--proto" => parsed.protos.push(value("--proto")?),
+            "-I" | "--include" => parsed.includes.push(value(&arg)?),
+            "--descriptor-set" => parsed.descriptor_set = Some(value("--descriptor-set")?),
+            "--out-dir" => parsed.out_dir = Some(value("--out-dir")?),
+            "--client" => parsed.client = true,
+            "--server" => parsed.server = true,
+            "--tls" => parsed.tls = true,
+            "--check" => parsed.check = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ if arg.starts_with("-I") => parsed.includes.push(PathBuf::from(&arg[2..])),
+            _ => return Err(format!("unexpected argument `{}`", arg)),
+        }
+    }
+    if parsed.out_dir.is_none() {
+        return Err(String::from("`--out-dir` is required"));
+    }
+    match (parsed.protos.is_empty(), &parsed.descriptor_set) {
+        (true, None) => return Err(String::from("either `--proto` or `--descriptor-set` is required")),
+        (false, Some(_)) => return Err(String::from("`--proto` and `--descriptor-set` are mutually exclusive")),
+        (true, Some(_)) if !parsed.includes.is_empty() => {
+            return Err(String::from("`-I` and `--descriptor-set` are mutually exclusive"))
+        }
+        _ => (),
+    }
+    if !parsed.client && !parsed.server {
+        parsed.client = true;
+        parsed.server = true;
+    }
+    Ok(parsed)
+}
+
+fn generate(args: &Args, out_dir: &Path) -> gen_tonic_impls::Result<()> {
+    let mut config = Config::new();
+    config.out_dir(out_dir).build_client(args.client).build_server(args.server).tls(args.tls);
+    match &args.descriptor_set {
+        Some(path) => config.compile_descriptor_set_file(path),
+        None => {
+            let includes = if args.includes.is_empty() {
+                vec![PathBuf::from(".")]
+            } else {
+                args.includes.clone()
+            };
+            config.compile(&args.protos, &includes)
+        }
+    }
+}
+
+/// Returns the generated files whose content differs from, or is missing in, `out_dir`.
+fn outdated_files(generated_dir: &Path, out_dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut outdated = vec![];
+    for entry in fs::read_dir(generated_dir)? {
+        let entry = entry?;
+        let target = out_dir.join(entry.file_name());
+        if fs::read(&target).ok() != Some(fs::read(entry.path())?) {
+            outdated.push(target);
+        }
+    }
+    outdated.sort();
+    Ok(outdated)
+}
+
+fn run(args: Args) -> Result<bool, String> {
+    let out_dir = args.out_dir.clone().unwrap_or_default();
+    if !args.check {
+        generate(&args, &out_dir).map_err(|e| e.to_string())?;
+        return Ok(true);
+    }
+    let generated_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+    generate(&args, generated_dir.path()).map_err(|e| e.to_string())?;
+    let outdated = outdated_files(generated_dir.path(), &out_dir).map_err(|e| e.to_string())?;
+    for path in &outdated {
+        eprintln!("{} is out of date", path.display());
+    }
+    Ok(outdated.is_empty())
+}
+
+fn main() {
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    match run(args) {
+        Ok(true) => (),
+        Ok(false) => process::exit(1),
+        Err(message) => {
+            eprintln!("error: {}", message);
+            process::exit(1);
+        }
+    }
+}
@@ -1,20 +1,31 @@
 use prost_types::FileDescriptorProto;
+use std::time::Duration;
 use quote::quote;
 
 use proc_macro2::{Ident, Literal, TokenStream};
 
-use crate::{FileProtoInfo, MethodProtoInfo, ServiceProtoInfo, };
+use crate::{common::generate_error_definitions, ident::to_snake, Config, FileProtoInfo, MethodProtoInfo, ServiceProtoInfo, TypeResolver};
 
-pub fn generate_grpc_client_impl(files: Vec<FileDescriptorProto>) -> String {
+pub(crate) fn generate_grpc_client_impl(files: Vec<FileDescriptorProto>, resolver: &TypeResolver, config: &Config) -> String {
     let use_statements = generate_use_statements();
+    let error_definitions = generate_error_definitions(config);
     let static_value_struct = generate_static_value_struct();
+    let event_helpers = generate_event_helpers(config);
+    let stream_structs = generate_stream_structs();
+    let in_flight_struct = generate_in_flight_struct();
+    let endpoint_config = generate_endpoint_config(config);
     let grpc_client_handler = generate_grpc_client_handler();
-    let client_handler_methods = generate_grpc_client_handler_methods(files.clone());
+    let client_handler_methods = generate_grpc_client_handler_methods(files.clone(), config);
     let tremor_grpc_client = generate_tremor_grpc_client();
-    let tremor_grpc_impl = generate_tremor_grpc_client_impls(files);
+    let tremor_grpc_impl = generate_tremor_grpc_client_impls(files, resolver, config);
     let code = quote! {
         #use_statements
+        #error_definitions
         #static_value_struct
+        #event_helpers
+        #stream_structs
+        #in_flight_struct
+        #endpoint_config
         #grpc_client_handler
         #client_handler_methods
         #tremor_grpc_client
@@ -27,9 +38,7 @@ pub fn generate_grpc_client_impl(files: Vec<FileDescriptorProto>) -> String {
 
 fn generate_use_statements () -> TokenStream {
     quote! {
-        use async_std::prelude::StreamExt;
         use value_trait::ValueAccess;
-        use crate::errors::{Error, ErrorKind, Result};
     }
 }
 
@@ -39,50 +48,671 @@ fn generate_static_value_struct() -> TokenStream {
     }
 }
 
+fn generate_event_helpers(config: &Config) -> TokenStream {
+    let timeout = config.get_runtime().timeout();
+    quote! {
+        // Request path and correlation data echoed in the meta of every reply to a request.
+        // `sequence` counts the replies sent so far, so stream replies can be put back in order.
+        #[derive(Debug, Clone)]
+        struct ReplyContext {
+            request_path: &'static str,
+            event_id: tremor_value::Value<'static>,
+            stream_id: Option<u64>,
+            correlation: Option<tremor_value::Value<'static>>,
+            sequence: u64
+        }
+
+        impl ReplyContext {
+            fn new(request_path: &'static str, event_id: &tremor_pipeline::EventId, meta: &tremor_value::Value) -> Self {
+                let mut id = tremor_value::value::Object::with_capacity(3);
+                id.insert("source_id".into(), event_id.source_id().into());
+                id.insert("stream_id".into(), event_id.stream_id().into());
+                id.insert("event_id".into(), event_id.event_id().into());
+                ReplyContext {
+                    request_path,
+                    event_id: tremor_value::Value::from(id),
+                    stream_id: meta.get_u64("stream_id"),
+                    correlation: meta.get("correlation").map(|correlation| correlation.clone_static()),
+                    sequence: 0
+                }
+            }
+
+            fn meta(&self, code: tonic::Code, message: &str) -> tremor_value::value::Object<'static> {
+                let mut meta = tremor_value::value::Object::with_capacity(9);
+                meta.insert("request_path".into(), self.request_path.into());
+                meta.insert("event_id".into(), self.event_id.clone());
+                meta.insert("sequence".into(), self.sequence.into());
+                if let Some(stream_id) = self.stream_id {
+                    meta.insert("stream_id".into(), stream_id.into());
+                }
+                if let Some(correlation) = &self.correlation {
+                    meta.insert("correlation".into(), correlation.clone());
+                }
+                meta.insert("grpc_status".into(), (code as i32).into());
+                meta.insert("grpc_message".into(), message.to_string().into());
+                meta
+            }
+
+            fn reply(&mut self, message: tremor_value::Value<'static>, headers: tremor_value::Value<'static>) -> tremor_script::EventPayload {
+                let mut meta = self.meta(tonic::Code::Ok, "");
+                meta.insert("headers".into(), headers);
+                self.sequence += 1;
+                (message, meta).into()
+            }
+
+            // Marks the end of a server stream.
+            fn end(&self, trailers: Option<&tonic::metadata::MetadataMap>) -> tremor_script::EventPayload {
+                let mut meta = self.meta(tonic::Code::Ok, "");
+                meta.insert("end_of_stream".into(), true.into());
+                if let Some(trailers) = trailers {
+                    meta.insert("trailers".into(), metadata_value(trailers));
+                }
+                (tremor_value::Value::Static(value_trait::StaticNode::Null), meta).into()
+            }
+
+            fn error(&self, status: &tonic::Status) -> tremor_script::EventPayload {
+                let mut meta = self.meta(status.code(), status.message());
+                meta.insert("trailers".into(), metadata_value(status.metadata()));
+                (tremor_value::Value::Static(value_trait::StaticNode::Null), meta).into()
+            }
+        }
+
+        // The inverse of `apply_headers`, repeated keys become arrays.
+        fn metadata_value(metadata: &tonic::metadata::MetadataMap) -> tremor_value::Value<'static> {
+            let mut object = tremor_value::value::Object::with_capacity(metadata.len());
+            for entry in metadata.iter() {
+                let (key, value) = match entry {
+                    tonic::metadata::KeyAndValueRef::Ascii(key, value) => match value.to_str() {
+                        Ok(value) => (key.as_str(), value.to_string()),
+                        Err(_) => continue,
+                    },
+                    tonic::metadata::KeyAndValueRef::Binary(key, value) => match value.to_bytes() {
+                        Ok(value) => (key.as_str(), base64::encode(value)),
+                        Err(_) => continue,
+                    },
+                };
+                match object.get_mut(key) {
+                    Some(tremor_value::Value::Array(values)) => values.push(value.into()),
+                    Some(existing) => {
+                        let first = std::mem::replace(existing, tremor_value::Value::Static(value_trait::StaticNode::Null));
+                        *existing = tremor_value::Value::from(vec![first, value.into()]);
+                    }
+                    None => {
+                        object.insert(key.to_string().into(), value.into());
+                    }
+                }
+            }
+            tremor_value::Value::from(object)
+        }
+
+        // Sets `grpc-timeout` from the `timeout_ms` or `deadline` (milliseconds since the Unix epoch)
+        // meta field, falling back to the method default, and returns the resulting deadline.
+        fn apply_timeout<T>(
+            request: &mut tonic::Request<T>,
+            meta: &tremor_value::Value,
+            default: Option<std::time::Duration>
+        ) -> Option<std::time::Instant> {
+            let timeout = if let Some(timeout_ms) = meta.get_u64("timeout_ms") {
+                Some(std::time::Duration::from_millis(timeout_ms))
+            } else if let Some(deadline) = meta.get_u64("deadline") {
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default();
+                Some(std::time::Duration::from_millis(deadline).checked_sub(now).unwrap_or_default())
+            } else {
+                default
+            }?;
+            request.set_timeout(timeout);
+            Some(std::time::Instant::now() + timeout)
+        }
+
+        async fn with_deadline<T, F>(deadline: Option<std::time::Instant>, future: F) -> std::result::Result<T, tonic::Status>
+        where
+            F: std::future::Future<Output = std::result::Result<T, tonic::Status>>
+        {
+            match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+                    match #timeout(remaining, future).await {
+                        // The server gives up at the same deadline, which tonic reports as cancelled.
+                        Ok(Err(_)) if std::time::Instant::now() >= deadline => {
+                            Err(tonic::Status::deadline_exceeded("deadline exceeded"))
+                        }
+                        Ok(result) => result,
+                        Err(_) => Err(tonic::Status::deadline_exceeded("deadline exceeded")),
+                    }
+                }
+                None => future.await,
+            }
+        }
+
+        // `meta.headers` maps each metadata key to a string or an array of strings, values of
+        // `-bin` keys are base64 encoded.
+        fn apply_headers(
+            metadata: &mut tonic::metadata::MetadataMap,
+            meta: &tremor_value::Value
+        ) -> std::result::Result<(), tonic::Status> {
+            let headers = match meta.get_object("headers") {
+                Some(headers) => headers,
+                None => return Ok(()),
+            };
+            for (key, values) in headers.iter() {
+                let values = match values.as_array() {
+                    Some(values) => values.iter().collect::<Vec<_>>(),
+                    None => vec![values],
+                };
+                for value in values {
+                    let value = value
+                        .as_str()
+                        .ok_or_else(|| tonic::Status::invalid_argument(format!("metadata `{}` must be a string", key)))?;
+                    if key.ends_with("-bin") {
+                        let value = base64::decode(value)
+                            .map_err(|e| tonic::Status::invalid_argument(format!("metadata `{}` is not valid base64: {}", key, e)))?;
+                        let key = tonic::metadata::BinaryMetadataKey::from_bytes(key.as_bytes())
+                            .map_err(|_| tonic::Status::invalid_argument(format!("invalid metadata key `{}`", key)))?;
+                        metadata.append_bin(key, tonic::metadata::MetadataValue::from_bytes(&value));
+                    } else {
+                        let value = value
+                            .parse()
+                            .map_err(|_| tonic::Status::invalid_argument(format!("invalid value for metadata `{}`", key)))?;
+                        let key = tonic::metadata::AsciiMetadataKey::from_bytes(key.as_bytes())
+                            .map_err(|_| tonic::Status::invalid_argument(format!("invalid metadata key `{}`", key)))?;
+                        metadata.append(key, value);
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+fn generate_stream_structs() -> TokenStream {
+    quote! {
+        // Client and bidirectional streams are driven by the `stream_id` and `stream_command` meta
+        // fields, ids are scoped to their method. `stream_command` is one of:
+        // - "open": starts a call, the event value is its first message unless null.
+        // - "data" (the default): sends the event value, opening the stream if it is unknown.
+        // - "half_close": sends the event value unless null, then ends the request stream. Replies
+        //   keep coming until the server ends the call. Replies with a `NOT_FOUND` error event if
+        //   the stream is not open, e.g. because it was cancelled or has ended.
+        // - "cancel": aborts the call, which replies with a `CANCELLED` error event. tonic ends the
+        //   request stream before it resets the call, so the server may see the stream complete.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        enum StreamCommand {
+            Open,
+            Data,
+            HalfClose,
+            Cancel
+        }
+
+        impl StreamCommand {
+            fn from_meta(meta: &tremor_value::Value) -> std::result::Result<Self, tonic::Status> {
+                match meta.get_str("stream_command") {
+                    None | Some("data") => Ok(StreamCommand::Data),
+                    Some("open") => Ok(StreamCommand::Open),
+                    Some("half_close") => Ok(StreamCommand::HalfClose),
+                    Some("cancel") => Ok(StreamCommand::Cancel),
+                    Some(command) => Err(tonic::Status::invalid_argument(format!("unknown stream command `{}`", command))),
+                }
+            }
+        }
+
+        // Whether the `method` streams of `event` have room for every value it sends, streams that
+        // are not open yet have `capacity`. Checked before sending anything, so a full stream
+        // rejects the whole event instead of the values after the ones already queued.
+        fn streams_have_room(
+            event: &tremor_pipeline::Event,
+            streams: &hashbrown::HashMap<StreamKey, StreamHandle>,
+            method: &'static str,
+            capacity: usize
+        ) -> bool {
+            let mut queued: hashbrown::HashMap<u64, usize> = hashbrown::HashMap::new();
+            for (value, meta) in event.value_meta_iter() {
+                let (stream_id, command) = match (meta.get_u64("stream_id"), StreamCommand::from_meta(meta)) {
+                    (Some(stream_id), Ok(command)) => (stream_id, command),
+                    _ => continue,
+                };
+                let is_null = matches!(value, tremor_value::Value::Static(value_trait::StaticNode::Null));
+                if command == StreamCommand::Data || (command != StreamCommand::Cancel && !is_null) {
+                    *queued.entry(stream_id).or_insert(0) += 1;
+                }
+            }
+            queued.into_iter().all(|(stream_id, count)| match streams.get(&(method, stream_id)) {
+                // Values sent to an ended stream are rejected, not queued.
+                Some(stream) if stream.tx.is_closed() => true,
+                Some(stream) => count <= stream.tx.capacity().unwrap_or(usize::MAX) - stream.tx.len(),
+                None => count <= capacity,
+            })
+        }
+
+        // Open streams are keyed by method path and `stream_id`, every method has its own ids.
+        type StreamKey = (&'static str, u64);
+
+        // The call task holds the receiving end of `cancel`, so the channel closes once the call
+        // has ended. Dropping the handle ends the request stream, calls are only cancelled through
+        // `cancel` or a handler shutdown.
+        #[derive(Debug)]
+        struct StreamHandle {
+            tx: async_std::channel::Sender<StaticValue>,
+            cancel: async_std::channel::Sender<tonic::Status>,
+            last_active: std::time::Instant
+        }
+
+        // Resolves to the output of `future`, or to the status sent on the cancel channel if that
+        // comes first.
+        struct Cancellable<F> {
+            future: std::pin::Pin<Box<F>>,
+            cancel: std::pin::Pin<Box<dyn std::future::Future<Output = tonic::Status> + Send>>
+        }
+
+        impl<T, F> std::future::Future for Cancellable<F>
+        where
+            F: std::future::Future<Output = std::result::Result<T, tonic::Status>>
+        {
+            type Output = std::result::Result<T, tonic::Status>;
+
+            fn poll(mut self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
+                if let std::task::Poll::Ready(status) = self.cancel.as_mut().poll(cx) {
+                    return std::task::Poll::Ready(Err(status));
+                }
+                self.future.as_mut().poll(cx)
+            }
+        }
+
+        // Request stream of a client or bidirectional call. A message that cannot be structurized
+        // cancels the call, ending the stream instead would make the server take the messages sent
+        // so far as the complete request.
+        struct RequestStream<T> {
+            rx: async_std::channel::Receiver<StaticValue>,
+            cancel: async_std::channel::Sender<tonic::Status>,
+            structurize: fn(tremor_value::Value<'static>) -> std::result::Result<T, tremor_value::Error>,
+            failed: bool
+        }
+
+        impl<T> async_std::stream::Stream for RequestStream<T> {
+            type Item = T;
+
+            fn poll_next(self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Option<T>> {
+                let this = self.get_mut();
+                if this.failed {
+                    // Stays pending until the call is cancelled.
+                    return std::task::Poll::Pending;
+                }
+                let value = match async_std::stream::Stream::poll_next(std::pin::Pin::new(&mut this.rx), cx) {
+                    std::task::Poll::Ready(Some(value)) => value,
+                    std::task::Poll::Ready(None) => return std::task::Poll::Ready(None),
+                    std::task::Poll::Pending => return std::task::Poll::Pending,
+                };
+                match (this.structurize)(value.0) {
+                    Ok(message) => std::task::Poll::Ready(Some(message)),
+                    Err(e) => {
+                        this.failed = true;
+                        let _ = this.cancel.try_send(tonic::Status::invalid_argument(e.to_string()));
+                        std::task::Poll::Pending
+                    }
+                }
+            }
+        }
+
+        fn cancellable<F>(cancel: &async_std::channel::Receiver<tonic::Status>, future: F) -> Cancellable<F> {
+            let cancel = cancel.clone();
+            Cancellable {
+                future: Box::pin(future),
+                cancel: Box::pin(async move {
+                    cancel.recv().await.unwrap_or_else(|_| tonic::Status::cancelled("client handler closed"))
+                })
+            }
+        }
+
+        // Stream calls are cancelled through their `StreamHandle` or when the handler shuts down.
+        fn cancellable_stream<F>(
+            cancel: &async_std::channel::Receiver<tonic::Status>,
+            shutdown: &async_std::channel::Receiver<tonic::Status>,
+            future: F
+        ) -> Cancellable<Cancellable<F>> {
+            cancellable(shutdown, cancellable(cancel, future))
+        }
+
+        // Sends a reply, only waiting for room in the reply channel until the handler shuts down,
+        // so a full reply channel cannot keep `GrpcClientHandler::close` or `drain` from stopping
+        // the task. Returns whether it was sent.
+        async fn send_reply(
+            reply_tx: &async_std::channel::Sender<tremor_script::EventPayload>,
+            shutdown: &async_std::channel::Receiver<tonic::Status>,
+            event: tremor_script::EventPayload
+        ) -> bool {
+            let event = match reply_tx.try_send(event) {
+                Ok(()) => return true,
+                Err(async_std::channel::TrySendError::Full(event)) => event,
+                Err(async_std::channel::TrySendError::Closed(_)) => return false,
+            };
+            let send = async move {
+                reply_tx.send(event).await.map_err(|_| tonic::Status::cancelled("reply channel closed"))
+            };
+            cancellable(shutdown, send).await.is_ok()
+        }
+    }
+}
+
+fn generate_in_flight_struct() -> TokenStream {
+    quote! {
+        // Held by every spawned call. Closing `shutdown` cancels the calls and any reply send they
+        // are blocked in, and once the last token is dropped `GrpcClientHandler::drain` returns.
+        #[derive(Debug, Clone)]
+        struct TaskToken {
+            shutdown: async_std::channel::Receiver<tonic::Status>,
+            _alive: async_std::channel::Sender<()>
+        }
+
+        // Counts the calls of a method that are still running, up to an optional limit.
+        #[derive(Debug, Clone)]
+        struct InFlight {
+            limit: Option<usize>,
+            count: std::sync::Arc<std::sync::atomic::AtomicUsize>
+        }
+
+        impl InFlight {
+            fn new(limit: Option<usize>) -> Self {
+                InFlight {
+                    limit,
+                    count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0))
+                }
+            }
+
+            // Takes `n` permits at once or none at all, so the values of an event are either all
+            // sent or all left for a retry.
+            fn try_acquire(&self, n: usize) -> Option<Vec<InFlightPermit>> {
+                let mut count = self.count.load(std::sync::atomic::Ordering::Acquire);
+                loop {
+                    if self.limit.map_or(false, |limit| count + n > limit) {
+                        return None;
+                    }
+                    match self.count.compare_exchange_weak(
+                        count,
+                        count + n,
+                        std::sync::atomic::Ordering::AcqRel,
+                        std::sync::atomic::Ordering::Acquire
+                    ) {
+                        Ok(_) => return Some((0..n).map(|_| InFlightPermit(self.count.clone())).collect()),
+                        Err(actual) => count = actual,
+                    }
+                }
+            }
+        }
+
+        #[derive(Debug)]
+        struct InFlightPermit(std::sync::Arc<std::sync::atomic::AtomicUsize>);
+
+        impl Drop for InFlightPermit {
+            fn drop(&mut self) {
+                self.0.fetch_sub(1, std::sync::atomic::Ordering::AcqRel);
+            }
+        }
+    }
+}
+
+fn generate_endpoint_config(config: &Config) -> TokenStream {
+    let tls_config = if config.get_tls() { generate_tls_config() } else { TokenStream::default() };
+    let (tls_field, tls_default, apply_tls) = if config.get_tls() {
+        (
+            quote! { pub tls: Option<TlsConfig> },
+            quote! { tls: None },
+            quote! {
+                if let Some(tls) = &self.tls {
+                    endpoint = endpoint.tls_config(tls.client_tls_config()?).map_err(into_error)?;
+                }
+            },
+        )
+    } else {
+        (TokenStream::default(), TokenStream::default(), TokenStream::default())
+    };
+    quote! {
+        #tls_config
+
+        // Channel settings left as `None` keep tonic's defaults. `rate_limit` is a number of requests
+        // per period.
+        #[derive(Debug, Clone)]
+        pub struct EndpointConfig {
+            pub uri: String,
+            pub connect_timeout: Option<std::time::Duration>,
+            pub http2_keep_alive_interval: Option<std::time::Duration>,
+            pub keep_alive_timeout: Option<std::time::Duration>,
+            pub initial_stream_window_size: Option<u32>,
+            pub initial_connection_window_size: Option<u32>,
+            pub concurrency_limit: Option<usize>,
+            pub rate_limit: Option<(u64, std::time::Duration)>,
+            pub tcp_nodelay: Option<bool>,
+            #tls_field
+        }
+
+        impl EndpointConfig {
+            pub fn new(uri: impl Into<String>) -> Self {
+                EndpointConfig {
+                    uri: uri.into(),
+                    connect_timeout: None,
+                    http2_keep_alive_interval: None,
+                    keep_alive_timeout: None,
+                    initial_stream_window_size: None,
+                    initial_connection_window_size: None,
+                    concurrency_limit: None,
+                    rate_limit: None,
+                    tcp_nodelay: None,
+                    #tls_default
+                }
+            }
+
+            fn endpoint(&self) -> Result<tonic::transport::Endpoint> {
+                let mut endpoint = tonic::transport::Endpoint::new(self.uri.clone()).map_err(into_error)?;
+                if let Some(timeout) = self.connect_timeout {
+                    endpoint = endpoint.connect_timeout(timeout);
+                }
+                if let Some(interval) = self.http2_keep_alive_interval {
+                    endpoint = endpoint.http2_keep_alive_interval(interval);
+                }
+                if let Some(timeout) = self.keep_alive_timeout {
+                    endpoint = endpoint.keep_alive_timeout(timeout);
+                }
+                if let Some(size) = self.initial_stream_window_size {
+                    endpoint = endpoint.initial_stream_window_size(size);
+                }
+                if let Some(size) = self.initial_connection_window_size {
+                    endpoint = endpoint.initial_connection_window_size(size);
+                }
+                if let Some(limit) = self.concurrency_limit {
+                    endpoint = endpoint.concurrency_limit(limit);
+                }
+                if let Some((limit, period)) = self.rate_limit {
+                    endpoint = endpoint.rate_limit(limit, period);
+                }
+                if let Some(nodelay) = self.tcp_nodelay {
+                    endpoint = endpoint.tcp_nodelay(nodelay);
+                }
+                #apply_tls
+                Ok(endpoint)
+            }
+        }
+
+        // Endpoints by fully qualified service name, e.g. `helloworld.Greeter`. Services without an
+        // entry use `default`.
+        #[derive(Debug, Clone, Default)]
+        pub struct Endpoints {
+            pub default: Option<EndpointConfig>,
+            pub services: hashbrown::HashMap<String, EndpointConfig>
+        }
+
+        impl Endpoints {
+            fn endpoint(&self, service: &str) -> Result<tonic::transport::Endpoint> {
+                let config = self.services.get(service).or_else(|| self.default.as_ref()).ok_or_else(|| {
+                    into_error(tonic::Status::invalid_argument(format!("no endpoint configured for `{}`", service)))
+                })?;
+                config.endpoint()
+            }
+        }
+    }
+}
+
+fn generate_tls_config() -> TokenStream {
+    quote! {
+        // PEM encoded certificates and key. A client certificate makes the connection use mutual
+        // TLS, `domain_name` overrides the name the server certificate is checked against.
+        #[derive(Debug, Clone, Default)]
+        pub struct TlsConfig {
+            pub ca_certificate: Option<Vec<u8>>,
+            pub client_certificate: Option<Vec<u8>>,
+            pub client_key: Option<Vec<u8>>,
+            pub domain_name: Option<String>
+        }
+
+        impl TlsConfig {
+            fn client_tls_config(&self) -> Result<tonic::transport::ClientTlsConfig> {
+                let mut tls = tonic::transport::ClientTlsConfig::new();
+                if let Some(ca_certificate) = &self.ca_certificate {
+                    tls = tls.ca_certificate(tonic::transport::Certificate::from_pem(ca_certificate));
+                }
+                match (&self.client_certificate, &self.client_key) {
+                    (Some(certificate), Some(key)) => {
+                        tls = tls.identity(tonic::transport::Identity::from_pem(certificate, key));
+                    }
+                    (None, None) => (),
+                    _ => {
+                        return Err(into_error(tonic::Status::invalid_argument(
+                            "client certificate and key must be set together"
+                        )));
+                    }
+                }
+                if let Some(domain_name) = &self.domain_name {
+                    tls = tls.domain_name(domain_name.clone());
+                }
+                Ok(tls)
+            }
+        }
+    }
+}
+
 fn generate_grpc_client_handler() -> TokenStream {
     quote! {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum RequestStatus {
+            Sent,
+            Busy,
+            StreamFull
+        }
+
         #[derive(Debug)]
         pub struct GrpcClientHandler {
             clients: hashbrown::HashMap<String, Box<dyn TremorGrpcClient>>,
-            senders: hashbrown::HashMap<u64, async_std::channel::Sender<StaticValue>>,
+            streams: hashbrown::HashMap<StreamKey, StreamHandle>,
             methods: hashbrown::HashMap<String, prost_types::MethodDescriptorProto>,
-            reply_tx: async_std::channel::Sender<tremor_script::EventPayload>
+            in_flight: hashbrown::HashMap<String, InFlight>,
+            reply_tx: async_std::channel::Sender<tremor_script::EventPayload>,
+            shutdown: async_std::channel::Sender<tonic::Status>,
+            tasks: Option<TaskToken>,
+            tasks_done: async_std::channel::Receiver<()>
         }
     }
 }
 
-fn generate_grpc_client_handler_methods(files: Vec<FileDescriptorProto>) -> TokenStream {
-    let connect_code = generate_grpc_client_handler_connect(files);
+fn generate_grpc_client_handler_methods(files: Vec<FileDescriptorProto>, config: &Config) -> TokenStream {
+    let connect_code = generate_grpc_client_handler_connect(files, config);
     let send_request_code = generate_client_handler_send_request();
+    let reap_streams_code = generate_client_handler_reap_streams(config);
+    let shutdown_code = generate_client_handler_shutdown(config);
     quote! {
         impl GrpcClientHandler {
             #connect_code
             #send_request_code
+            #reap_streams_code
+            #shutdown_code
+        }
+    }
+}
+
+fn generate_client_handler_shutdown(config: &Config) -> TokenStream {
+    let timeout = config.get_runtime().timeout();
+    quote! {
+        // Cancels every call still running, each one replies with a `CANCELLED` error event if the
+        // reply channel has room for it. Dropping the handler does the same.
+        pub fn close(self) {
+            self.shutdown.close();
+        }
+
+        // Half-closes every open client stream and waits up to `timeout` for the running calls to
+        // send their replies, then cancels the remaining ones along with any reply they are still
+        // waiting to send. Returns whether every call finished in time.
+        pub async fn drain(mut self, timeout: std::time::Duration) -> bool {
+            for stream in self.streams.values() {
+                stream.tx.close();
+            }
+            self.tasks = None;
+            // Only fails once every task has dropped its token.
+            let drained = #timeout(timeout, self.tasks_done.recv()).await.is_ok();
+            self.close();
+            drained
         }
     }
 }
 
-fn generate_grpc_client_handler_connect(files: Vec<FileDescriptorProto>) -> TokenStream {
+/// Exact `Duration` expression for `duration`, down to the nanosecond.
+fn duration_tokens(duration: Duration) -> TokenStream {
+    let secs = Literal::u64_unsuffixed(duration.as_secs());
+    let nanos = Literal::u32_unsuffixed(duration.subsec_nanos());
+    quote! { std::time::Duration::new(#secs, #nanos) }
+}
+
+fn generate_client_handler_reap_streams(config: &Config) -> TokenStream {
+    let idle_timeout = match config.get_stream_idle_timeout() {
+        Some(timeout) => {
+            let timeout = duration_tokens(timeout);
+            quote! { Some(#timeout) }
+        }
+        None => quote! { None },
+    };
+    quote! {
+        // Forgets the streams whose call has ended and cancels the ones that have not received an
+        // event for longer than the idle timeout. Half-closed streams are never idle.
+        pub fn reap_streams(&mut self) {
+            let idle_timeout: Option<std::time::Duration> = #idle_timeout;
+            let now = std::time::Instant::now();
+            self.streams.retain(|_, stream| {
+                if stream.cancel.is_closed() {
+                    return false;
+                }
+                let idle = idle_timeout.map_or(false, |timeout| {
+                    !stream.tx.is_closed() && now.duration_since(stream.last_active) >= timeout
+                });
+                if idle {
+                    let _ = stream.cancel.try_send(tonic::Status::cancelled("stream idle"));
+                }
+                !idle
+            });
+        }
+    }
+}
+
+fn generate_grpc_client_handler_connect(files: Vec<FileDescriptorProto>, config: &Config) -> TokenStream {
     let mut clients = vec![];
     let mut methods = vec![];
+    let mut limits = vec![];
     for file in files {
-        let package_ident = file.package_ident();
+        let package_path = file.package_path();
         for service in &file.service {
             let client_mod_ident = service.client_mod_ident();
             let client_ident = service.client_ident();
-            let client_identifier = quote::format_ident!("{}_{}", package_ident, client_mod_ident);
-            let client_key_token = Literal::string(&format!("{}.{}", package_ident, service.name()));
+            let service_name = file.service_name(service);
+            let client_identifier = quote::format_ident!("{}", to_snake(&format!("{}_{}", file.package().replace('.', "_"), client_mod_ident)));
+            let client_key_token = Literal::string(&service_name);
             clients.push(quote! {
-                let #client_identifier = #package_ident::#client_mod_ident::#client_ident::connect(addr.clone()).await?;
+                let channel = endpoints.endpoint(#client_key_token)?.connect_lazy().map_err(into_error)?;
+                let #client_identifier = #package_path #client_mod_ident::#client_ident::new(channel);
                 client_map.insert(String::from(#client_key_token), Box::new(#client_identifier));
             });
             for method in &service.method {
-                let method_key_token = Literal::string(&format!("{}.{}/{}", package_ident, service.name(), method.name()));
+                let method_key_token = Literal::string(&file.method_path(service, method));
                 let method_name = Literal::string(method.name());
                 let method_name_expr = quote! {Some(String::from(#method_name))}; 
-                let input_type_name = Literal::string(method.request_message_ident(file.package()).to_string().as_str());
+                let input_type_name = Literal::string(method.input_type());
                 let input_type_expr = quote! {Some(String::from(#input_type_name))};
-                let output_type_name = Literal::string(method.response_message_ident(file.package()).to_string().as_str());
+                let output_type_name = Literal::string(method.output_type());
                 let output_type_expr = quote! {Some(String::from(#output_type_name))};
                 let client_streaming = quote::format_ident!("{}", method.client_streaming());
                 let client_streaming_expr = quote! {Some(#client_streaming)};
@@ -98,6 +728,16 @@ fn generate_grpc_client_handler_connect(files: Vec<FileDescriptorProto>) -> Toke
                         options: None
                     });
                 });
+                let limit = match config.get_concurrency_limit(&file.method_path(service, method)) {
+                    Some(limit) => {
+                        let limit = Literal::usize_unsuffixed(limit);
+                        quote! { Some(#limit) }
+                    }
+                    None => quote! { None },
+                };
+                limits.push(quote! {
+                    in_flight_map.insert(String::from(#method_key_token), InFlight::new(#limit));
+                });
             }
         }
     }
@@ -105,44 +745,70 @@ fn generate_grpc_client_handler_connect(files: Vec<FileDescriptorProto>) -> Toke
     clients_code.extend(clients);
     let mut methods_code = TokenStream::default();
     methods_code.extend(methods);
+    let mut limits_code = TokenStream::default();
+    limits_code.extend(limits);
     quote! {
-        pub async fn connect(addr: String, reply_tx: async_std::channel::Sender<tremor_script::EventPayload>) -> crate::errors::Result<Self> {
+        // Channels connect on first use, so an unavailable backend only fails the calls to its
+        // services.
+        pub fn connect(endpoints: &Endpoints, reply_tx: async_std::channel::Sender<tremor_script::EventPayload>) -> Result<Self> {
             let mut client_map: hashbrown::HashMap<String, Box<dyn TremorGrpcClient>> = hashbrown::HashMap::new();
             let mut methods_map: hashbrown::HashMap<String, prost_types::MethodDescriptorProto> = hashbrown::HashMap::new();
-            let senders_map: hashbrown::HashMap<u64, async_std::channel::Sender<StaticValue>> = hashbrown::HashMap::new();
+            let streams_map: hashbrown::HashMap<StreamKey, StreamHandle> = hashbrown::HashMap::new();
+            let mut in_flight_map: hashbrown::HashMap<String, InFlight> = hashbrown::HashMap::new();
+            let (shutdown, shutdown_rx) = async_std::channel::bounded::<tonic::Status>(1);
+            let (alive, tasks_done) = async_std::channel::bounded::<()>(1);
 
             #clients_code
             #methods_code
+            #limits_code
 
-            GrpcClientHandler {
+            Ok(GrpcClientHandler {
                 clients: client_map,
                 methods: methods_map,
-                senders: senders_map,
-                reply_tx
-            }
+                streams: streams_map,
+                in_flight: in_flight_map,
+                reply_tx,
+                shutdown,
+                tasks: Some(TaskToken {
+                    shutdown: shutdown_rx,
+                    _alive: alive
+                }),
+                tasks_done
+            })
         }
     }
 }
 
 fn generate_client_handler_send_request() -> TokenStream {
     quote! {
-        pub async fn send_request(&mut self, event: tremor_pipeline::Event) {
-            let request_path = event.value_meta_iter().next().and_then(|(_, meta)| meta.get_str("request_path"));
+        // Unary and server streaming calls run in their own tasks, `RequestStatus::Busy` means the
+        // method has no room for every value of the event and none was sent. `RequestStatus::StreamFull`
+        // means the same for the client streams of the event. Either way the event can be sent
+        // again later.
+        pub async fn send_request(&mut self, event: &tremor_pipeline::Event) -> Result<RequestStatus> {
+            self.reap_streams();
+            let tasks = match &self.tasks {
+                Some(tasks) => tasks,
+                None => return Ok(RequestStatus::Sent),
+            };
+            let request_path = event.value_meta_iter().next().and_then(|(_, meta)| meta.get_str("request_path")).map(String::from);
             if let Some(path) = request_path {
                 let client_key = path.split("/").collect::<Vec<&str>>()[0];
-                if let Some((method, client)) = self.methods.get(path).zip(self.clients.get_mut(client_key)) {
-                    if !method.client_streaming() && !method.server_streaming() {
-                        client.send_unary_request(event, self.reply_tx.clone()).await;
+                if let Some(((method, client), in_flight)) = self.methods.get(&path).zip(self.clients.get_mut(client_key)).zip(self.in_flight.get(&path)) {
+                    let status = if !method.client_streaming() && !method.server_streaming() {
+                        client.send_unary_request(&path, event, self.reply_tx.clone(), tasks, in_flight).await?
                     }
                     else if method.client_streaming() && !method.server_streaming() {
-                        client.send_client_stream_request(event, self.reply_tx.clone(), &mut self.senders).await;
+                        client.send_client_stream_request(&path, event, self.reply_tx.clone(), tasks, &mut self.streams).await?
                     } else if !method.client_streaming() && method.server_streaming() {
-                        client.send_server_stream_request(event, self.reply_tx.clone()).await;
+                        client.send_server_stream_request(&path, event, self.reply_tx.clone(), tasks, in_flight).await?
                     } else {
-                        client.send_binary_stream_request(event, self.reply_tx.clone(), &mut self.senders).await;
-                    }
+                        client.send_binary_stream_request(&path, event, self.reply_tx.clone(), tasks, &mut self.streams).await?
+                    };
+                    return Ok(status);
                 }
             }
+            Ok(RequestStatus::Sent)
         }
     }
 }
@@ -153,69 +819,87 @@ fn generate_tremor_grpc_client() -> TokenStream {
         trait TremorGrpcClient: Send  + std::fmt::Debug {
             async fn send_unary_request(
                 &mut self,
-                _event: tremor_pipeline::Event,
-                _reply_tx: async_std::channel::Sender<tremor_script::EventPayload>
-            ) -> crate::errors::Result<()> {
-                Ok(())
+                _method: &str,
+                _event: &tremor_pipeline::Event,
+                _reply_tx: async_std::channel::Sender<tremor_script::EventPayload>,
+                _tasks: &TaskToken,
+                _in_flight: &InFlight
+            ) -> Result<RequestStatus> {
+                Ok(RequestStatus::Sent)
             }
             async fn send_client_stream_request(
                 &mut self,
-                _event: tremor_pipeline::Event,
+                _method: &str,
+                _event: &tremor_pipeline::Event,
                 _reply_tx: async_std::channel::Sender<tremor_script::EventPayload>,
-                _senders: &mut hashbrown::HashMap<u64, async_std::channel::Sender<StaticValue>>
-            ) -> crate::errors::Result<()> {
-                Ok(())
+                _tasks: &TaskToken,
+                _streams: &mut hashbrown::HashMap<StreamKey, StreamHandle>
+            ) -> Result<RequestStatus> {
+                Ok(RequestStatus::Sent)
             }
             async fn send_server_stream_request(
                 &mut self,
-                _event: tremor_pipeline::Event,
-                _reply_tx: async_std::channel::Sender<tremor_script::EventPayload>
-            ) -> crate::errors::Result<()> {
-                Ok(())
+                _method: &str,
+                _event: &tremor_pipeline::Event,
+                _reply_tx: async_std::channel::Sender<tremor_script::EventPayload>,
+                _tasks: &TaskToken,
+                _in_flight: &InFlight
+            ) -> Result<RequestStatus> {
+                Ok(RequestStatus::Sent)
             }
             async fn send_binary_stream_request(
                 &mut self,
-                _event: tremor_pipeline::Event,
+                _method: &str,
+                _event: &tremor_pipeline::Event,
                 _reply_tx: async_std::channel::Sender<tremor_script::EventPayload>,
-                _senders: &mut hashbrown::HashMap<u64, async_std::channel::Sender<StaticValue>>
-            ) -> crate::errors::Result<()> {
-                Ok(())
+                _tasks: &TaskToken,
+                _streams: &mut hashbrown::HashMap<StreamKey, StreamHandle>
+            ) -> Result<RequestStatus> {
+                Ok(RequestStatus::Sent)
             }
         }
     }
 }
 
-fn generate_tremor_grpc_client_impls(files: Vec<FileDescriptorProto>) -> TokenStream {
+fn generate_tremor_grpc_client_impls(files: Vec<FileDescriptorProto>, resolver: &TypeResolver, config: &Config) -> TokenStream {
+    let spawn = config.get_runtime().spawn();
     let mut trait_impls = vec![];
     for file in files {
-        let package_ident = file.package_ident();
+        let package_path = file.package_path();
         for service in &file.service {
             let client_mod_ident = service.client_mod_ident();
             let client_ident = service.client_ident();
-            let mut send_unary_request_code = TokenStream::default();
-            let mut send_client_stream_request_code = TokenStream::default();
-            let mut send_server_stream_request_code = TokenStream::default();
-            let mut send_binary_stream_request_code = TokenStream::default();
+            let mut unary_arms = vec![];
+            let mut client_stream_arms = vec![];
+            let mut server_stream_arms = vec![];
+            let mut binary_stream_arms = vec![];
             for method in &service.method {
-                let request_message_ident = method.request_message_ident(file.package());
-                let response_message_ident = method.response_message_ident(file.package());
+                let method_path = Literal::string(&file.method_path(service, method));
+                let request_message_path = method.request_message_path(resolver);
+                let response_message_path = method.response_message_path(resolver);
                 let method_ident = method.name_ident();
-                if !method.server_streaming() && !method.client_streaming() {
-                    send_unary_request_code = generate_send_unary_request(package_ident.clone(), method_ident.clone(), request_message_ident.clone(), response_message_ident.clone());
-                } 
-                if !method.server_streaming() && method.client_streaming() {
-                    send_client_stream_request_code = generate_send_client_stream_request(package_ident.clone(), method_ident.clone(), request_message_ident.clone(), response_message_ident.clone());
-                }
-                if method.server_streaming() && !method.client_streaming() {
-                    send_server_stream_request_code = generate_send_server_stream_request(package_ident.clone(), method_ident.clone(), request_message_ident.clone(), response_message_ident.clone());
-                }
-                if method.server_streaming() && method.client_streaming() {
-                    send_binary_stream_request_code = generate_send_binary_stream_request(package_ident.clone(), method_ident.clone(), request_message_ident.clone(), response_message_ident.clone());
+                let default_timeout = match config.get_timeout(&file.method_path(service, method)) {
+                    Some(timeout) => {
+                        let timeout = duration_tokens(timeout);
+                        quote! { Some(#timeout) }
+                    }
+                    None => quote! { None },
+                };
+                let capacity = Literal::usize_unsuffixed(config.get_stream_capacity(&file.method_path(service, method)));
+                match (method.client_streaming(), method.server_streaming()) {
+                    (false, false) => unary_arms.push(generate_send_unary_request_arm(spawn.clone(), method_path, method_ident, default_timeout, request_message_path, response_message_path)),
+                    (true, false) => client_stream_arms.push(generate_send_client_stream_request_arm(spawn.clone(), method_path, method_ident, default_timeout, capacity, request_message_path, response_message_path)),
+                    (false, true) => server_stream_arms.push(generate_send_server_stream_request_arm(spawn.clone(), method_path, method_ident, default_timeout, request_message_path, response_message_path)),
+                    (true, true) => binary_stream_arms.push(generate_send_binary_stream_request_arm(spawn.clone(), method_path, method_ident, default_timeout, capacity, request_message_path, response_message_path)),
                 }
             }
+            let send_unary_request_code = generate_send_unary_request(unary_arms);
+            let send_client_stream_request_code = generate_send_client_stream_request(client_stream_arms);
+            let send_server_stream_request_code = generate_send_server_stream_request(server_stream_arms);
+            let send_binary_stream_request_code = generate_send_binary_stream_request(binary_stream_arms);
             trait_impls.push(quote! {
                 #[async_trait::async_trait]
-                impl TremorGrpcClient for #package_ident::#client_mod_ident::#client_ident<tonic::transport::Channel> {
+                impl TremorGrpcClient for #package_path #client_mod_ident::#client_ident<tonic::transport::Channel> {
                     #send_unary_request_code
 
                     #send_client_stream_request_code
@@ -232,189 +916,345 @@ fn generate_tremor_grpc_client_impls(files: Vec<FileDescriptorProto>) -> TokenSt
     code
 }
 
-fn generate_send_unary_request(package_ident: Ident, method_ident: Ident, request_message_ident: Ident, response_message_ident: Ident) -> TokenStream {
+fn generate_send_unary_request(arms: Vec<TokenStream>) -> TokenStream {
+    if arms.is_empty() {
+        return TokenStream::default();
+    }
     quote! {
-        async fn send_unary_request(&mut self, event: tremor_pipeline::Event, reply_tx: async_std::channel::Sender<tremor_script::EventPayload>) -> crate::errors::Result<()> {
-            if !event.is_batch {
-                for (value, meta) in event.value_meta_iter() {
-                    println!("args: {:?} {:?}", value, meta);
-                    let body: #package_ident::#request_message_ident = tremor_value::structurize(value.clone_static())?;
-                    let mut request = tonic::Request::new(body.clone());
-                    println!("req: {:?}, body: {:?}", request, body);
-                    let metadata = request.metadata_mut();
-                    if let Some(headers) = meta.get_str("headers") {
-                        metadata.insert("headers", headers.parse().unwrap());
-                    } else {
-                        metadata.insert("headers", "none".parse().unwrap());
+        async fn send_unary_request(
+            &mut self,
+            method: &str,
+            event: &tremor_pipeline::Event,
+            reply_tx: async_std::channel::Sender<tremor_script::EventPayload>,
+            tasks: &TaskToken,
+            in_flight: &InFlight
+        ) -> Result<RequestStatus> {
+            match method {
+                #(#arms)*
+                _ => Ok(RequestStatus::Sent)
+            }
+        }
+    }
+}
+
+fn generate_send_unary_request_arm(spawn: TokenStream, method_path: Literal, method_ident: Ident, default_timeout: TokenStream, request_message_path: TokenStream, response_message_path: TokenStream) -> TokenStream {
+    quote! {
+        #method_path => {
+            let permits = match in_flight.try_acquire(event.value_meta_iter().count()) {
+                Some(permits) => permits,
+                None => return Ok(RequestStatus::Busy),
+            };
+            for ((value, meta), permit) in event.value_meta_iter().zip(permits) {
+                let mut context = ReplyContext::new(#method_path, &event.id, meta);
+                let body: std::result::Result<#request_message_path, _> = tremor_value::structurize(value.clone_static());
+                let body = match body {
+                    Ok(body) => body,
+                    Err(e) => {
+                        let status = tonic::Status::invalid_argument(e.to_string());
+                        reply_tx.send(context.error(&status)).await.map_err(into_error)?;
+                        continue;
                     }
-                    let resp: tonic::Response<#package_ident::#response_message_ident> = self.#method_ident(request).await?;
-                    println!("resp: {:?}", resp);
-                    let message = tremor_value::to_value(resp.into_inner())?;
-                    println!("response serialized: {:?}", message);
-                    let response_meta = tremor_value::value::Object::with_capacity(1);
-                    let event: tremor_script::EventPayload = (message, response_meta).into();
-                    reply_tx.send(event).await?;
-                }
-            } 
-            Ok(())
+                };
+                let mut request = tonic::Request::new(body);
+                if let Err(status) = apply_headers(request.metadata_mut(), meta) {
+                    reply_tx.send(context.error(&status)).await.map_err(into_error)?;
+                    continue;
+                }
+                let deadline = apply_timeout(&mut request, meta, #default_timeout);
+                let mut client = self.clone();
+                let reply_tx = reply_tx.clone();
+                let tasks = tasks.clone();
+                #spawn(async move {
+                    let result: std::result::Result<tonic::Response<#response_message_path>, tonic::Status> = cancellable(&tasks.shutdown, with_deadline(deadline, client.#method_ident(request))).await;
+                    let event = match result.and_then(|resp| {
+                        let headers = metadata_value(resp.metadata());
+                        let message = tremor_value::to_value(resp.into_inner()).map_err(|e| tonic::Status::internal(e.to_string()))?;
+                        Ok((message, headers))
+                    }) {
+                        Ok((message, headers)) => context.reply(message, headers),
+                        Err(status) => context.error(&status),
+                    };
+                    // The call is over, its slot is free before the caller hears about it.
+                    drop(permit);
+                    // A closed reply channel means the handler is gone, nobody is left to notify.
+                    let _ = send_reply(&reply_tx, &tasks.shutdown, event).await;
+                });
+            }
+            Ok(RequestStatus::Sent)
         }
     }
 }
 
-fn generate_send_client_stream_request(package_ident: Ident, method_ident: Ident, request_message_ident: Ident, response_message_ident: Ident) -> TokenStream {
+fn generate_send_client_stream_request(arms: Vec<TokenStream>) -> TokenStream {
+    if arms.is_empty() {
+        return TokenStream::default();
+    }
     quote! {
         async fn send_client_stream_request(
             &mut self,
-            event: tremor_pipeline::Event,
+            method: &str,
+            event: &tremor_pipeline::Event,
             reply_tx: async_std::channel::Sender<tremor_script::EventPayload>,
-            senders: &mut hashbrown::HashMap<u64, async_std::channel::Sender<StaticValue>>
-        ) {
-            if !event.is_batch {
-                for (value, meta) in event.value_meta_iter() {
-                    let mut client = self.clone();
-                    let reply_tx = reply_tx.clone();
-                    let value = value.clone_static();
-                    let meta = meta.clone_static();
-                    println!("value: {:?}, meta: {:?} \n", value, meta);
-                    if let (stream_id) = meta.get_u64("stream_id") {
-                        println!("stream_id: {:?}", stream_id);
-                        if let Some(tx) = senders.get(&stream_id) {
-                            println!("tx: {:?}", tx);
-                            tx.send(StaticValue(value)).await.unwrap();
-                            if meta.contains_key("flag") {
-                                println!("flagggg");
-                                tx.close();
-                            } 
-                        } else {
-                            let (tx, rx) = async_std::channel::unbounded::<StaticValue>();
-                            senders.insert(stream_id.clone(), tx.clone());
-                            println!("senders: {:?}", &senders);
-                            let rx = rx.map(|val| {
-                                let structured_value: #package_ident::#request_message_ident = tremor_value::structurize(val.0).unwrap();
-                                structured_value
-                            });
-                            // let rx = rx.map(mat());
-                            let mut request = tonic::Request::new(rx);
-                            let metadata = request.metadata_mut();
-                            if let Some(headers) = meta.get_str("headers") {
-                                metadata.insert("headers", headers.parse().unwrap());
-                            } else {
-                                metadata.insert("headers", "none".parse().unwrap());
-                            }
-                            async_std::task::spawn(async move {
-                                tx.send(StaticValue(value)).await.unwrap();
-                            });
-                            async_std::task::spawn(async move {
-                                let resp: tonic::Response<#package_ident::#response_message_ident> = client.#method_ident(request).await.unwrap();
-                                println!("resp: {:?}", resp);
-                                let message = tremor_value::to_value(resp.into_inner()).unwrap();
-                                println!("response serialized: {:?}", message);
-                                let response_meta = tremor_value::value::Object::with_capacity(1);
-                                let response_event: tremor_script::EventPayload = (message, response_meta).into();
-                                reply_tx.send(response_event).await.unwrap();
-                            });
-                        }
-                    } 
-                }
+            tasks: &TaskToken,
+            streams: &mut hashbrown::HashMap<StreamKey, StreamHandle>
+        ) -> Result<RequestStatus> {
+            match method {
+                #(#arms)*
+                _ => Ok(RequestStatus::Sent)
             }
-            Ok(())
         }
     }
 }
 
-fn generate_send_server_stream_request(package_ident: Ident, method_ident: Ident, request_message_ident: Ident, response_message_ident: Ident) -> TokenStream {
+fn generate_send_client_stream_request_arm(spawn: TokenStream, method_path: Literal, method_ident: Ident, default_timeout: TokenStream, capacity: Literal, request_message_path: TokenStream, response_message_path: TokenStream) -> TokenStream {
+    let call = quote! {
+        let result: std::result::Result<tonic::Response<#response_message_path>, tonic::Status> = cancellable_stream(&cancel_rx, &tasks.shutdown, with_deadline(deadline, client.#method_ident(request))).await;
+        let event = match result.and_then(|resp| {
+            let headers = metadata_value(resp.metadata());
+            let message = tremor_value::to_value(resp.into_inner()).map_err(|e| tonic::Status::internal(e.to_string()))?;
+            Ok((message, headers))
+        }) {
+            Ok((message, headers)) => context.reply(message, headers),
+            Err(status) => context.error(&status),
+        };
+        // A closed reply channel means the handler is gone, nobody is left to notify.
+        let _ = send_reply(&reply_tx, &tasks.shutdown, event).await;
+    };
+    generate_send_stream_request_arm(spawn, method_path, default_timeout, capacity, request_message_path, call)
+}
+
+fn generate_send_server_stream_request(arms: Vec<TokenStream>) -> TokenStream {
+    if arms.is_empty() {
+        return TokenStream::default();
+    }
     quote! {
         async fn send_server_stream_request(
             &mut self,
-            event: tremor_pipeline::Event,
-            reply_tx: async_std::channel::Sender<tremor_script::EventPayload>
-        ) {
-            for (value, meta) in event.value_meta_iter() {
-                println!("args: {:?} {:?}", value, meta);
-                let body: #package_ident::#request_message_ident = tremor_value::structurize(value.clone_static()).unwrap();
-                let mut request = tonic::Request::new(body.clone());
-                println!("req: {:?}, body: {:?}", request, body);
-                let metadata = request.metadata_mut();
-                if let Some(headers) = meta.get_str("headers") {
-                    metadata.insert("headers", headers.parse().unwrap());
-                } else {
-                    metadata.insert("headers", "none".parse().unwrap());
-                }
-                let resp: tonic::Response<tonic::Streaming<#package_ident::#response_message_ident>> = self.#method_ident(request).await.unwrap();
-                println!("resp: {:?}", resp);
-                let mut stream = resp.into_inner();
-                while let Some(item) = stream.message().await.unwrap() {
-                    println!("item: {:?}", item);
-                    let message = tremor_value::to_value(item).unwrap();
-                    println!("response serialized: {:?}", message);
-                    let response_meta = tremor_value::value::Object::with_capacity(1);
-                    let event: tremor_script::EventPayload = (message, response_meta).into();
-                    reply_tx.send(event).await.unwrap();
+            method: &str,
+            event: &tremor_pipeline::Event,
+            reply_tx: async_std::channel::Sender<tremor_script::EventPayload>,
+            tasks: &TaskToken,
+            in_flight: &InFlight
+        ) -> Result<RequestStatus> {
+            match method {
+                #(#arms)*
+                _ => Ok(RequestStatus::Sent)
+            }
+        }
+    }
+}
+
+fn generate_send_server_stream_request_arm(spawn: TokenStream, method_path: Literal, method_ident: Ident, default_timeout: TokenStream, request_message_path: TokenStream, response_message_path: TokenStream) -> TokenStream {
+    quote! {
+        #method_path => {
+            let permits = match in_flight.try_acquire(event.value_meta_iter().count()) {
+                Some(permits) => permits,
+                None => return Ok(RequestStatus::Busy),
+            };
+            for ((value, meta), permit) in event.value_meta_iter().zip(permits) {
+                let mut context = ReplyContext::new(#method_path, &event.id, meta);
+                let body: std::result::Result<#request_message_path, _> = tremor_value::structurize(value.clone_static());
+                let body = match body {
+                    Ok(body) => body,
+                    Err(e) => {
+                        let status = tonic::Status::invalid_argument(e.to_string());
+                        reply_tx.send(context.error(&status)).await.map_err(into_error)?;
+                        continue;
+                    }
+                };
+                let mut request = tonic::Request::new(body);
+                if let Err(status) = apply_headers(request.metadata_mut(), meta) {
+                    reply_tx.send(context.error(&status)).await.map_err(into_error)?;
+                    continue;
                 }
+                let deadline = apply_timeout(&mut request, meta, #default_timeout);
+                let mut client = self.clone();
+                let reply_tx = reply_tx.clone();
+                let tasks = tasks.clone();
+                #spawn(async move {
+                    let resp: tonic::Response<tonic::Streaming<#response_message_path>> = match cancellable(&tasks.shutdown, with_deadline(deadline, client.#method_ident(request))).await {
+                        Ok(resp) => resp,
+                        Err(status) => {
+                            drop(permit);
+                            let _ = send_reply(&reply_tx, &tasks.shutdown, context.error(&status)).await;
+                            return;
+                        }
+                    };
+                    let headers = metadata_value(resp.metadata());
+                    let mut stream = resp.into_inner();
+                    let mut permit = Some(permit);
+                    loop {
+                        let (event, done) = match cancellable(&tasks.shutdown, with_deadline(deadline, stream.message())).await {
+                            Ok(Some(item)) => match tremor_value::to_value(item) {
+                                Ok(message) => (context.reply(message, headers.clone()), false),
+                                Err(e) => (context.error(&tonic::Status::internal(e.to_string())), true),
+                            },
+                            Ok(None) => match cancellable(&tasks.shutdown, with_deadline(deadline, stream.trailers())).await {
+                                Ok(trailers) => (context.end(trailers.as_ref()), true),
+                                Err(status) => (context.error(&status), true),
+                            },
+                            Err(status) => (context.error(&status), true),
+                        };
+                        // The call is over, its slot is free before the caller hears about it.
+                        if done {
+                            permit.take();
+                        }
+                        // A closed reply channel means the handler is gone, nobody is left to notify.
+                        if !send_reply(&reply_tx, &tasks.shutdown, event).await || done {
+                            break;
+                        }
+                    }
+                });
             }
-            Ok(())
+            Ok(RequestStatus::Sent)
         }
     }
 }
 
-fn generate_send_binary_stream_request(package_ident: Ident, method_ident: Ident, request_message_ident: Ident, response_message_ident: Ident) -> TokenStream {
+fn generate_send_binary_stream_request(arms: Vec<TokenStream>) -> TokenStream {
+    if arms.is_empty() {
+        return TokenStream::default();
+    }
     quote! {
         async fn send_binary_stream_request(
             &mut self,
-            event: tremor_pipeline::Event,
+            method: &str,
+            event: &tremor_pipeline::Event,
             reply_tx: async_std::channel::Sender<tremor_script::EventPayload>,
-            senders: &mut hashbrown::HashMap<u64, async_std::channel::Sender<StaticValue>>
-        ) {
+            tasks: &TaskToken,
+            streams: &mut hashbrown::HashMap<StreamKey, StreamHandle>
+        ) -> Result<RequestStatus> {
+            match method {
+                #(#arms)*
+                _ => Ok(RequestStatus::Sent)
+            }
+        }
+    }
+}
+
+fn generate_send_binary_stream_request_arm(spawn: TokenStream, method_path: Literal, method_ident: Ident, default_timeout: TokenStream, capacity: Literal, request_message_path: TokenStream, response_message_path: TokenStream) -> TokenStream {
+    let call = quote! {
+        let resp: tonic::Response<tonic::Streaming<#response_message_path>> = match cancellable_stream(&cancel_rx, &tasks.shutdown, with_deadline(deadline, client.#method_ident(request))).await {
+            Ok(resp) => resp,
+            Err(status) => {
+                let _ = send_reply(&reply_tx, &tasks.shutdown, context.error(&status)).await;
+                return;
+            }
+        };
+        let headers = metadata_value(resp.metadata());
+        let mut stream = resp.into_inner();
+        loop {
+            let (event, done) = match cancellable_stream(&cancel_rx, &tasks.shutdown, with_deadline(deadline, stream.message())).await {
+                Ok(Some(item)) => match tremor_value::to_value(item) {
+                    Ok(message) => (context.reply(message, headers.clone()), false),
+                    Err(e) => (context.error(&tonic::Status::internal(e.to_string())), true),
+                },
+                Ok(None) => match cancellable_stream(&cancel_rx, &tasks.shutdown, with_deadline(deadline, stream.trailers())).await {
+                    Ok(trailers) => (context.end(trailers.as_ref()), true),
+                    Err(status) => (context.error(&status), true),
+                },
+                Err(status) => (context.error(&status), true),
+            };
+            // A closed reply channel means the handler is gone, nobody is left to notify.
+            if !send_reply(&reply_tx, &tasks.shutdown, event).await || done {
+                break;
+            }
+        }
+    };
+    generate_send_stream_request_arm(spawn, method_path, default_timeout, capacity, request_message_path, call)
+}
+
+/// Feeds the events of a client or bidirectional stream into the request stream of its call,
+/// opening the stream and spawning `call` on the first event.
+fn generate_send_stream_request_arm(spawn: TokenStream, method_path: Literal, default_timeout: TokenStream, capacity: Literal, request_message_path: TokenStream, call: TokenStream) -> TokenStream {
+    quote! {
+        #method_path => {
+            if !streams_have_room(event, streams, #method_path, #capacity) {
+                return Ok(RequestStatus::StreamFull);
+            }
             for (value, meta) in event.value_meta_iter() {
-                let mut client = self.clone();
-                let reply_tx = reply_tx.clone();
                 let value = value.clone_static();
                 let meta = meta.clone_static();
-                println!("value: {:?}, meta: {:?} \n", value, meta);
-                if let Some(stream_id) = meta.get_u64("stream_id") {
-                    println!("stream_id: {:?}", stream_id);
-                    if let Some(tx) = senders.get(&stream_id) {
-                        println!("tx: {:?}", tx);
-                        tx.send(StaticValue(value)).await.unwrap();
-                        if let Some(_flag) = meta.get_u64("flag") {
-                            println!("flagggg");
-                            tx.close();
-                        } 
-                    } else {
-                        let (tx, rx) = async_std::channel::unbounded::<StaticValue>();
-                        senders.insert(stream_id.clone(), tx.clone());
-                        println!("senders: {:?}", &senders);
-                        let rx = rx.map(|val| {
-                            let structured_value: #package_ident::#request_message_ident = tremor_value::structurize(val.0).unwrap();
-                            structured_value
-                        });
-                        let mut request = tonic::Request::new(rx);
-                        let metadata = request.metadata_mut();
-                        if let Some(headers) = meta.get_str("headers") {
-                            metadata.insert("headers", headers.parse().unwrap());
-                        } else {
-                            metadata.insert("headers", "none".parse().unwrap());
-                        }
-                        async_std::task::spawn(async move {
-                            tx.send(StaticValue(value)).await.unwrap();
-                        });
-                        async_std::task::spawn(async move {
-                            let resp: tonic::Response<tonic::Streaming<#package_ident::#response_message_ident>> = client.#method_ident(request).await.unwrap();
-                            println!("resp: {:?}", resp);
-                            let mut stream = resp.into_inner();
-                            while let Some(item) = stream.message().await.unwrap() {
-                                println!("item: {:?}", item);
-                                let message = tremor_value::to_value(item).unwrap();
-                                println!("response serialized: {:?}", message);
-                                let response_meta = tremor_value::value::Object::with_capacity(1);
-                                let event: tremor_script::EventPayload = (message, response_meta).into();
-                                reply_tx.send(event).await.unwrap();
-                            }
-                        });
+                let context = ReplyContext::new(#method_path, &event.id, &meta);
+                let stream_id = match meta.get_u64("stream_id") {
+                    Some(stream_id) => stream_id,
+                    None => {
+                        let status = tonic::Status::invalid_argument("missing `stream_id`");
+                        reply_tx.send(context.error(&status)).await.map_err(into_error)?;
+                        continue;
                     }
-                } 
+                };
+                let key = (#method_path, stream_id);
+                let command = match StreamCommand::from_meta(&meta) {
+                    Ok(command) => command,
+                    Err(status) => {
+                        reply_tx.send(context.error(&status)).await.map_err(into_error)?;
+                        continue;
+                    }
+                };
+                if command == StreamCommand::Cancel {
+                    if let Some(stream) = streams.remove(&key) {
+                        let _ = stream.cancel.try_send(tonic::Status::cancelled("stream cancelled"));
+                    }
+                    continue;
+                }
+                if command == StreamCommand::Open && streams.contains_key(&key) {
+                    let status = tonic::Status::already_exists("stream already open");
+                    reply_tx.send(context.error(&status)).await.map_err(into_error)?;
+                    continue;
+                }
+                if !streams.contains_key(&key) {
+                    if command == StreamCommand::HalfClose {
+                        let status = tonic::Status::not_found("stream is not open");
+                        reply_tx.send(context.error(&status)).await.map_err(into_error)?;
+                        continue;
+                    }
+                    let (tx, rx) = async_std::channel::bounded::<StaticValue>(#capacity);
+                    let (cancel_tx, cancel_rx) = async_std::channel::bounded::<tonic::Status>(1);
+                    let rx: RequestStream<#request_message_path> = RequestStream {
+                        rx,
+                        cancel: cancel_tx.clone(),
+                        structurize: tremor_value::structurize,
+                        failed: false
+                    };
+                    let mut request = tonic::Request::new(rx);
+                    if let Err(status) = apply_headers(request.metadata_mut(), &meta) {
+                        reply_tx.send(context.error(&status)).await.map_err(into_error)?;
+                        continue;
+                    }
+                    let deadline = apply_timeout(&mut request, &meta, #default_timeout);
+                    let mut client = self.clone();
+                    let reply_tx = reply_tx.clone();
+                    let mut context = context.clone();
+                    let tasks = tasks.clone();
+                    #spawn(async move {
+                        #call
+                    });
+                    streams.insert(key, StreamHandle {
+                        tx,
+                        cancel: cancel_tx,
+                        last_active: std::time::Instant::now()
+                    });
+                }
+                let stream = match streams.get_mut(&key) {
+                    Some(stream) => stream,
+                    None => continue,
+                };
+                stream.last_active = std::time::Instant::now();
+                let is_null = matches!(value, tremor_value::Value::Static(value_trait::StaticNode::Null));
+                if command == StreamCommand::Data || !is_null {
+                    // Cannot be full, `streams_have_room` checked the room up front.
+                    if stream.tx.try_send(StaticValue(value)).is_err() {
+                        let status = tonic::Status::failed_precondition("stream already ended");
+                        reply_tx.send(context.error(&status)).await.map_err(into_error)?;
+                        continue;
+                    }
+                }
+                if command == StreamCommand::HalfClose {
+                    stream.tx.close();
+                }
             }
-            Ok(())
+            Ok(RequestStatus::Sent)
         }
     }
 }
@@ -0,0 +1,84 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::{Config, ErrorConversion};
+
+/// Emits the `Error` and `Result` types the generated code returns, plus the `into_error`
+/// function every fallible call site converts its error with.
+///
+/// Without a configured error type a self-contained `Error` enum is emitted, otherwise the
+/// configured type is imported and converted into according to its [`ErrorConversion`].
+pub fn generate_error_definitions(config: &Config) -> TokenStream {
+    let (error_definition, conversion) = match config.get_error_type() {
+        Some((error_path, conversion)) => (quote! { use #error_path as Error; }, conversion),
+        None => (generate_error_enum(), ErrorConversion::From),
+    };
+    let into_error = match conversion {
+        ErrorConversion::From => quote! {
+            fn into_error<E>(e: E) -> Error where Error: From<E> {
+                Error::from(e)
+            }
+        },
+        ErrorConversion::Display => quote! {
+            fn into_error<E: std::fmt::Display>(e: E) -> Error {
+                Error::from(e.to_string())
+            }
+        },
+    };
+    quote! {
+        #error_definition
+
+        pub type Result<T> = std::result::Result<T, Error>;
+
+        #into_error
+    }
+}
+
+fn generate_error_enum() -> TokenStream {
+    quote! {
+        #[derive(Debug)]
+        pub enum Error {
+            Status(tonic::Status),
+            Transport(tonic::transport::Error),
+            Value(tremor_value::Error),
+            ChannelClosed,
+        }
+
+        impl std::fmt::Display for Error {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match self {
+                    Error::Status(status) => write!(f, "gRPC call failed: {}", status),
+                    Error::Transport(e) => write!(f, "gRPC transport error: {}", e),
+                    Error::Value(e) => write!(f, "value conversion failed: {}", e),
+                    Error::ChannelClosed => write!(f, "channel closed"),
+                }
+            }
+        }
+
+        impl std::error::Error for Error {}
+
+        impl From<tonic::Status> for Error {
+            fn from(status: tonic::Status) -> Self {
+                Error::Status(status)
+            }
+        }
+
+        impl From<tonic::transport::Error> for Error {
+            fn from(e: tonic::transport::Error) -> Self {
+                Error::Transport(e)
+            }
+        }
+
+        impl From<tremor_value::Error> for Error {
+            fn from(e: tremor_value::Error) -> Self {
+                Error::Value(e)
+            }
+        }
+
+        impl<T> From<async_std::channel::SendError<T>> for Error {
+            fn from(_: async_std::channel::SendError<T>) -> Self {
+                Error::ChannelClosed
+            }
+        }
+    }
+}
@@ -0,0 +1,378 @@
+use proc_macro2::TokenStream;
+use prost::Message;
+use prost_types::{FileDescriptorProto, FileDescriptorSet};
+use quote::quote;
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use crate::{
+    apply_rustfmt, client, gen_file_descriptor, gen_tonic_mod, proto_file_names, read_file, server, Error, FileProtoInfo, Result,
+    TypeResolver,
+};
+
+/// Number of messages a client stream buffers unless configured with [`Config::stream_capacity`].
+pub const DEFAULT_STREAM_CAPACITY: usize = 64;
+
+/// The async runtime the generated code spawns its tasks on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Runtime {
+    AsyncStd,
+    Tokio,
+}
+
+impl Runtime {
+    /// Path of the function used to spawn a future on this runtime.
+    pub(crate) fn spawn(&self) -> TokenStream {
+        match self {
+            Runtime::AsyncStd => quote! { async_std::task::spawn },
+            Runtime::Tokio => quote! { tokio::spawn },
+        }
+    }
+
+    /// Path of the function used to put a time limit on a future on this runtime.
+    pub(crate) fn timeout(&self) -> TokenStream {
+        match self {
+            Runtime::AsyncStd => quote! { async_std::future::timeout },
+            Runtime::Tokio => quote! { tokio::time::timeout },
+        }
+    }
+}
+
+/// How the generated code converts errors from tonic, `tremor_value` and channel sends into the
+/// configured error type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorConversion {
+    /// Through `From` impls on the error type for each source error: `tonic::Status`,
+    /// `tonic::transport::Error`, `tremor_value::Error` and `async_std::channel::SendError<T>`.
+    From,
+    /// Through a single `From<String>` impl, passing the `Display` output of the source error.
+    Display,
+}
+
+/// Configuration options for generating the tremor gRPC client and server bindings.
+#[derive(Debug, Clone)]
+pub struct Config {
+    out_dir: Option<PathBuf>,
+    client_file_name: String,
+    server_file_name: String,
+    build_client: bool,
+    build_server: bool,
+    files: Vec<String>,
+    services: Vec<String>,
+    methods: Vec<String>,
+    edition: String,
+    format: bool,
+    runtime: Runtime,
+    error_type: Option<(String, ErrorConversion)>,
+    timeouts: HashMap<String, Duration>,
+    concurrency_limits: HashMap<String, usize>,
+    stream_capacities: HashMap<String, usize>,
+    stream_idle_timeout: Option<Duration>,
+    tls: bool,
+}
+
+impl Config {
+    /// Creates a new configuration with default options.
+    pub fn new() -> Self {
+        Config::default()
+    }
+
+    /// Sets the directory the generated files are written to.
+    ///
+    /// Defaults to the `OUT_DIR` environment variable set by cargo for build scripts.
+    pub fn out_dir(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.out_dir = Some(path.into());
+        self
+    }
+
+    /// Sets the name of the generated client file. Defaults to `grpc_client.rs`.
+    pub fn client_file_name(&mut self, name: impl Into<String>) -> &mut Self {
+        self.client_file_name = name.into();
+        self
+    }
+
+    /// Sets the name of the generated server file. Defaults to `grpc_server.rs`.
+    pub fn server_file_name(&mut self, name: impl Into<String>) -> &mut Self {
+        self.server_file_name = name.into();
+        self
+    }
+
+    /// Enables or disables client code generation. Enabled by default.
+    pub fn build_client(&mut self, enable: bool) -> &mut Self {
+        self.build_client = enable;
+        self
+    }
+
+    /// Enables or disables server code generation. Enabled by default.
+    pub fn build_server(&mut self, enable: bool) -> &mut Self {
+        self.build_server = enable;
+        self
+    }
+
+    /// Restricts generation to the given fully qualified service, e.g. `helloworld.Greeter`.
+    ///
+    /// May be called multiple times. If never called, every service is generated.
+    pub fn service(&mut self, name: impl Into<String>) -> &mut Self {
+        self.services.push(name.into());
+        self
+    }
+
+    /// Restricts the client to the given method path, e.g. `helloworld.Greeter/SayHello`. Services
+    /// without any of the given methods are left out of the client.
+    ///
+    /// May be called multiple times. If never called, every method of the included services is
+    /// generated. The server always implements every method of its services, as tonic requires.
+    pub fn method(&mut self, path: impl Into<String>) -> &mut Self {
+        self.methods.push(path.into());
+        self
+    }
+
+    /// Restricts generation to the services of the given file, named as in the descriptor set,
+    /// e.g. `acme/billing/v1/invoices.proto`.
+    ///
+    /// May be called multiple times. If never called, the files passed to [`Config::compile`] are
+    /// generated.
+    pub fn file(&mut self, name: impl Into<String>) -> &mut Self {
+        self.files.push(name.into());
+        self
+    }
+
+    /// Sets the Rust edition passed to `rustfmt`. Defaults to `2018`.
+    pub fn edition(&mut self, edition: impl Into<String>) -> &mut Self {
+        self.edition = edition.into();
+        self
+    }
+
+    /// Enables or disables running `rustfmt` on the generated files. Enabled by default.
+    pub fn format(&mut self, enable: bool) -> &mut Self {
+        self.format = enable;
+        self
+    }
+
+    /// Sets the async runtime targeted by the generated code. Defaults to `async-std`.
+    pub fn runtime(&mut self, runtime: Runtime) -> &mut Self {
+        self.runtime = runtime;
+        self
+    }
+
+    /// Makes the generated code return the host crate's error type at `path`, e.g.
+    /// `crate::errors::Error`, instead of emitting its own `Error` enum.
+    pub fn error_type(&mut self, path: impl Into<String>, conversion: ErrorConversion) -> &mut Self {
+        self.error_type = Some((path.into(), conversion));
+        self
+    }
+
+    /// Sets the default timeout of calls to the method at `path`, e.g.
+    /// `helloworld.Greeter/SayHello`.
+    ///
+    /// Events can override it with a `timeout_ms` or `deadline` (milliseconds since the Unix epoch)
+    /// meta field. Calls without any of these have no timeout.
+    pub fn timeout(&mut self, path: impl Into<String>, timeout: Duration) -> &mut Self {
+        self.timeouts.insert(path.into(), timeout);
+        self
+    }
+
+    /// Limits the number of concurrent calls to the unary or server streaming method at `path`.
+    ///
+    /// If the values of an event would exceed it, `send_request` returns `RequestStatus::Busy`
+    /// without sending any of them, and the caller can retry the event as soon as the last reply of
+    /// a call has arrived. A batch larger than `limit` is never sent. Unlimited by default.
+    pub fn concurrency_limit(&mut self, path: impl Into<String>, limit: usize) -> &mut Self {
+        self.concurrency_limits.insert(path.into(), limit);
+        self
+    }
+
+    /// Sets the number of messages buffered for each open stream of the client or bidirectional
+    /// streaming method at `path`. Defaults to [`DEFAULT_STREAM_CAPACITY`].
+    ///
+    /// If a stream has no room for every value an event sends to it, `send_request` returns
+    /// `RequestStatus::StreamFull` without sending any of the event's values, and the caller can
+    /// retry the event once the stream has caught up.
+    pub fn stream_capacity(&mut self, path: impl Into<String>, capacity: usize) -> &mut Self {
+        self.stream_capacities.insert(path.into(), capacity);
+        self
+    }
+
+    /// Cancels client and bidirectional streams that have not received an event for `timeout`.
+    ///
+    /// Idle streams are reaped on each `send_request` and `reap_streams` call. Streams are never
+    /// reaped by default.
+    pub fn stream_idle_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.stream_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Enables or disables generating TLS settings on the client's `EndpointConfig`. The generated
+    /// code then needs tonic's `tls` feature. Disabled by default.
+    pub fn tls(&mut self, enable: bool) -> &mut Self {
+        self.tls = enable;
+        self
+    }
+
+    pub(crate) fn get_runtime(&self) -> Runtime {
+        self.runtime
+    }
+
+    pub(crate) fn get_timeout(&self, path: &str) -> Option<Duration> {
+        self.timeouts.get(path).copied()
+    }
+
+    pub(crate) fn get_concurrency_limit(&self, path: &str) -> Option<usize> {
+        self.concurrency_limits.get(path).copied()
+    }
+
+    pub(crate) fn get_stream_capacity(&self, path: &str) -> usize {
+        self.stream_capacities.get(path).copied().unwrap_or(DEFAULT_STREAM_CAPACITY)
+    }
+
+    pub(crate) fn get_stream_idle_timeout(&self) -> Option<Duration> {
+        self.stream_idle_timeout
+    }
+
+    pub(crate) fn get_tls(&self) -> bool {
+        self.tls
+    }
+
+    pub(crate) fn get_error_type(&self) -> Option<(TokenStream, ErrorConversion)> {
+        let (path, conversion) = self.error_type.as_ref()?;
+        // Validated in `generate`.
+        Some((TokenStream::from_str(path).ok()?, *conversion))
+    }
+
+    /// Compiles the `.proto` files and writes the generated bindings to the output directory.
+    pub fn compile(&self, protos: &[impl AsRef<Path>], includes: &[impl AsRef<Path>]) -> Result<()> {
+        let descriptor_set = gen_file_descriptor(protos, includes)?;
+        self.generate(descriptor_set, Some(proto_file_names(protos, includes)))
+    }
+
+    /// Reads an encoded `FileDescriptorSet` (e.g. a `.binpb` or `.desc` file produced by
+    /// `protoc --descriptor_set_out` or `buf build`) and generates the bindings for it.
+    pub fn compile_descriptor_set_file(&self, path: impl AsRef<Path>) -> Result<()> {
+        let buf = read_file(path.as_ref())?;
+        self.compile_descriptor_set(FileDescriptorSet::decode(&*buf).map_err(Error::Decode)?)
+    }
+
+    /// Generates the bindings for an already compiled `FileDescriptorSet`, without invoking
+    /// `protoc`.
+    ///
+    /// Unless restricted with [`Config::file`], services are generated for every file in the set
+    /// that no other file in the set imports.
+    pub fn compile_descriptor_set(&self, descriptor_set: FileDescriptorSet) -> Result<()> {
+        self.generate(descriptor_set, None)
+    }
+
+    fn generate(&self, descriptor_set: FileDescriptorSet, targets: Option<Vec<String>>) -> Result<()> {
+        let out_dir = match &self.out_dir {
+            Some(out_dir) => out_dir.clone(),
+            None => PathBuf::from(env::var("OUT_DIR").map_err(Error::OutDir)?),
+        };
+        if let Some((path, _)) = &self.error_type {
+            TokenStream::from_str(path)
+                .map_err(|_| Error::InvalidConfig(format!("`{}` is not a valid error type path", path)))?;
+        }
+        if let Some((path, _)) = self.stream_capacities.iter().find(|(_, capacity)| **capacity == 0) {
+            return Err(Error::InvalidConfig(format!("stream capacity of `{}` must not be 0", path)));
+        }
+        let resolver = TypeResolver::new(&descriptor_set.file);
+        let files = self.filter_services(self.target_files(descriptor_set.file, targets));
+        resolver.check_defined(&files)?;
+        let packages = resolver.required_packages(&files);
+        if self.build_client {
+            let grpc_client_code = client::generate_grpc_client_impl(self.filter_methods(files.clone()), &resolver, self);
+            self.write_output(out_dir.join(&self.client_file_name), &packages, &grpc_client_code)?;
+        }
+        if self.build_server {
+            let grpc_server_code = server::generate_grpc_server_impl(files, &resolver, self);
+            self.write_output(out_dir.join(&self.server_file_name), &packages, &grpc_server_code)?;
+        }
+        Ok(())
+    }
+
+    fn write_output(&self, output_file: PathBuf, packages: &[String], code: &str) -> Result<()> {
+        let mut buf = String::new();
+        let tonic_modules = format!("{}", gen_tonic_mod(packages));
+        buf.push_str(&tonic_modules);
+        buf.push_str(code);
+        fs::write(&output_file, buf).map_err(|source| Error::Io {
+            path: output_file.clone(),
+            source,
+        })?;
+        if self.format {
+            apply_rustfmt(&output_file, &self.edition)?;
+        }
+        Ok(())
+    }
+
+    /// Drops the files that were only pulled in as dependencies, their types are still resolved
+    /// through the [`TypeResolver`].
+    fn target_files(&self, mut files: Vec<FileDescriptorProto>, targets: Option<Vec<String>>) -> Vec<FileDescriptorProto> {
+        if !self.files.is_empty() {
+            files.retain(|file| self.files.iter().any(|name| name == file.name()));
+        } else if let Some(targets) = targets {
+            files.retain(|file| targets.iter().any(|name| name == file.name()));
+        } else {
+            let imported = files
+                .iter()
+                .flat_map(|file| file.dependency.clone())
+                .collect::<HashSet<String>>();
+            files.retain(|file| !imported.contains(file.name()));
+        }
+        files
+    }
+
+    fn filter_services(&self, mut files: Vec<FileDescriptorProto>) -> Vec<FileDescriptorProto> {
+        for file in &mut files {
+            let mut services = std::mem::take(&mut file.service);
+            services.retain(|service| self.services.is_empty() || self.services.contains(&file.service_name(service)));
+            file.service = services;
+        }
+        files
+    }
+
+    /// Only applied to the client, tonic server traits need every method implemented.
+    fn filter_methods(&self, mut files: Vec<FileDescriptorProto>) -> Vec<FileDescriptorProto> {
+        if self.methods.is_empty() {
+            return files;
+        }
+        for file in &mut files {
+            let mut services = std::mem::take(&mut file.service);
+            for service in &mut services {
+                let mut methods = std::mem::take(&mut service.method);
+                methods.retain(|method| self.methods.contains(&file.method_path(service, method)));
+                service.method = methods;
+            }
+            services.retain(|service| !service.method.is_empty());
+            file.service = services;
+        }
+        files
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            out_dir: None,
+            client_file_name: String::from("grpc_client.rs"),
+            server_file_name: String::from("grpc_server.rs"),
+            build_client: true,
+            build_server: true,
+            files: Vec::new(),
+            services: Vec::new(),
+            methods: Vec::new(),
+            edition: String::from("2018"),
+            format: true,
+            runtime: Runtime::AsyncStd,
+            error_type: None,
+            timeouts: HashMap::new(),
+            concurrency_limits: HashMap::new(),
+            stream_capacities: HashMap::new(),
+            stream_idle_timeout: None,
+            tls: false,
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::{env, fmt, io, path::PathBuf, process::ExitStatus};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors returned while generating the bindings.
+#[derive(Debug)]
+pub enum Error {
+    /// `protoc` could not be started, e.g. because it is not installed.
+    ProtocSpawn(io::Error),
+    /// `protoc` exited unsuccessfully, usually because of an error in a `.proto` file.
+    Protoc(ProtocError),
+    /// The file descriptor set could not be decoded.
+    Decode(prost::DecodeError),
+    /// The protos use a construct the generator cannot handle.
+    Unsupported {
+        file: String,
+        service: Option<String>,
+        method: Option<String>,
+        reason: String,
+    },
+    /// Reading or writing `path` failed.
+    Io { path: PathBuf, source: io::Error },
+    /// A [`Config`](crate::Config) option has an invalid value.
+    InvalidConfig(String),
+    /// No output directory was configured and `OUT_DIR` is not set.
+    OutDir(env::VarError),
+    /// `rustfmt` could not be run on `path` or rejected it.
+    Rustfmt { path: PathBuf, message: String },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::ProtocSpawn(e) => write!(f, "failed to invoke protoc: {}", e),
+            Error::Protoc(e) => write!(f, "{}", e),
+            Error::Decode(e) => write!(f, "invalid file descriptor set: {}", e),
+            Error::Unsupported { file, service, method, reason } => {
+                write!(f, "unsupported construct in {}", file)?;
+                if let Some(service) = service {
+                    write!(f, ", service {}", service)?;
+                }
+                if let Some(method) = method {
+                    write!(f, ", method {}", method)?;
+                }
+                write!(f, ": {}", reason)
+            }
+            Error::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::InvalidConfig(message) => write!(f, "invalid configuration: {}", message),
+            Error::OutDir(e) => write!(f, "no output directory configured and OUT_DIR is unusable: {}", e),
+            Error::Rustfmt { path, message } => write!(f, "rustfmt failed on {}: {}", path.display(), message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::ProtocSpawn(e) => Some(e),
+            Error::Protoc(e) => Some(e),
+            Error::Decode(e) => Some(e),
+            Error::Io { source, .. } => Some(source),
+            Error::OutDir(e) => Some(e),
+            Error::Unsupported { .. } | Error::InvalidConfig(_) | Error::Rustfmt { .. } => None,
+        }
+    }
+}
+
+/// `protoc` exited unsuccessfully while building the file descriptor set.
+#[derive(Debug)]
+pub struct ProtocError {
+    /// The full command line `protoc` was invoked with.
+    pub command: String,
+    /// The include paths passed to `protoc`.
+    pub includes: Vec<PathBuf>,
+    /// The exit status of `protoc`.
+    pub status: ExitStatus,
+    /// Everything `protoc` wrote to stderr, usually `file:line:column: message` diagnostics.
+    pub stderr: String,
+}
+
+impl fmt::Display for ProtocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "protoc failed with {}", self.status)?;
+        writeln!(f, "command: {}", self.command)?;
+        let includes = self.includes.iter().map(|include| include.display().to_string()).collect::<Vec<String>>();
+        writeln!(f, "includes: [{}]", includes.join(", "))?;
+        write!(f, "{}", self.stderr.trim_end())
+    }
+}
+
+impl std::error::Error for ProtocError {}
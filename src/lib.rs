@@ -1,24 +1,28 @@
-pub mod client;
+mod client;
+mod common;
+mod config;
+mod error;
 mod ident;
+mod server;
+
+pub use config::{Config, ErrorConversion, Runtime, DEFAULT_STREAM_CAPACITY};
+pub use error::{Error, ProtocError, Result};
 
 use heck::SnakeCase;
 use ident::{to_snake, to_upper_camel};
 use itertools::Itertools;
-use proc_macro2::{Ident, TokenStream};
+use proc_macro2::{Ident, Literal, TokenStream};
 use prost::Message;
 use prost_build::protoc;
-use prost_types::{FileDescriptorProto, FileDescriptorSet, MethodDescriptorProto, ServiceDescriptorProto};
+use prost_types::{DescriptorProto, FileDescriptorProto, FileDescriptorSet, MethodDescriptorProto, ServiceDescriptorProto};
 use quote::quote;
 use std::{
-    ffi::OsStr,
+    collections::{HashMap, HashSet},
     fs,
     path::{Path, PathBuf},
     process::Command,
-    str::FromStr,
 };
 
-type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
-
 trait ServiceProtoInfo {
     fn client_ident(&self) -> Ident;
     fn server_ident(&self) -> Ident;
@@ -29,17 +33,36 @@ trait ServiceProtoInfo {
 
 trait MethodProtoInfo {
     fn name_ident(&self) -> Ident;
-    fn request_message_ident(&self, package: &str) -> Ident;
-    fn response_message_ident(&self, package: &str) -> Ident;
+    fn stream_type_ident(&self) -> Ident;
+    fn request_message_path(&self, resolver: &TypeResolver) -> TokenStream;
+    fn response_message_path(&self, resolver: &TypeResolver) -> TokenStream;
 }
 
 trait FileProtoInfo {
-    fn package_ident(&self) -> Ident;
+    fn package_path(&self) -> TokenStream;
+    fn service_name(&self, service: &ServiceDescriptorProto) -> String;
+    fn method_path(&self, service: &ServiceDescriptorProto, method: &MethodDescriptorProto) -> String;
 }
 
 impl FileProtoInfo for FileDescriptorProto {
-    fn package_ident(&self) -> Ident {
-        quote::format_ident!("{}", self.package())
+    /// Module path of the package relative to the generated file, including the trailing `::`.
+    fn package_path(&self) -> TokenStream {
+        let segments = package_segments(self.package());
+        quote! { #(#segments::)* }
+    }
+
+    /// Fully qualified service name, e.g. `acme.billing.v1.Invoices`.
+    fn service_name(&self, service: &ServiceDescriptorProto) -> String {
+        if self.package().is_empty() {
+            service.name().to_string()
+        } else {
+            format!("{}.{}", self.package(), service.name())
+        }
+    }
+
+    /// gRPC method path without the leading slash, e.g. `acme.billing.v1.Invoices/Create`.
+    fn method_path(&self, service: &ServiceDescriptorProto, method: &MethodDescriptorProto) -> String {
+        format!("{}/{}", self.service_name(service), method.name())
     }
 }
 
@@ -70,46 +93,91 @@ impl MethodProtoInfo for MethodDescriptorProto {
         quote::format_ident!("{}", self.name().to_snake_case())
     }
 
-    fn request_message_ident(&self, package: &str) -> Ident {
-        quote::format_ident!("{}", get_message_type(package, self.input_type()))
+    fn stream_type_ident(&self) -> Ident {
+        quote::format_ident!("{}Stream", self.name())
     }
 
-    fn response_message_ident(&self, package: &str) -> Ident {
-        quote::format_ident!("{}", get_message_type(package, self.output_type()))
+    fn request_message_path(&self, resolver: &TypeResolver) -> TokenStream {
+        resolver.resolve(self.input_type())
     }
+
+    fn response_message_path(&self, resolver: &TypeResolver) -> TokenStream {
+        resolver.resolve(self.output_type())
+    }
+}
+
+
+/// Generates the client and/or server bindings into `out_dir` using the default [`Config`].
+pub fn generate(protos: &[impl AsRef<Path>], includes: &[impl AsRef<Path>], out_dir: impl Into<PathBuf>, server: bool, client: bool) -> Result<()> {
+    Config::new()
+        .out_dir(out_dir)
+        .build_server(server)
+        .build_client(client)
+        .compile(protos, includes)
 }
 
+/// Generates the bindings from a precompiled `FileDescriptorSet` using the default [`Config`].
+pub fn generate_from_descriptor_set(descriptor_set: FileDescriptorSet, out_dir: impl Into<PathBuf>, server: bool, client: bool) -> Result<()> {
+    Config::new()
+        .out_dir(out_dir)
+        .build_server(server)
+        .build_client(client)
+        .compile_descriptor_set(descriptor_set)
+}
 
-pub fn generate(protos: &[impl AsRef<Path>], includes: &[impl AsRef<Path>], out_dir: impl Into<PathBuf>, _server: bool, client: bool) -> Result<()> {
-    let descriptor_set = gen_file_descriptor(protos, includes)?;
-    if client {
-        let output_file = out_dir.into().join("grpc_client.rs");
-        fs::OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(output_file.clone())?;
-        let mut buf = String::new();
-        let tonic_modules = format!("{}", gen_tonic_mod(descriptor_set.file.clone()));
-        buf.push_str(&tonic_modules);
-        let grpc_client_code = client::generate_grpc_client_impl(descriptor_set.file.clone()); 
-        buf.push_str(&grpc_client_code);
-        fs::write(output_file.clone(), buf)?;
-        apply_rustfmt(output_file, "2018")?;
+/// Generates the bindings from an encoded `FileDescriptorSet` file using the default [`Config`].
+pub fn generate_from_descriptor_set_file(path: impl AsRef<Path>, out_dir: impl Into<PathBuf>, server: bool, client: bool) -> Result<()> {
+    Config::new()
+        .out_dir(out_dir)
+        .build_server(server)
+        .build_client(client)
+        .compile_descriptor_set_file(path)
+}
+
+fn gen_tonic_mod(packages: &[String]) -> TokenStream {
+    let packages = packages
+        .iter()
+        .unique()
+        .sorted_by(|a, b| a.split('.').cmp(b.split('.')))
+        .collect::<Vec<&String>>();
+    let mut code = TokenStream::new();
+    if packages.iter().any(|package| package.is_empty()) {
+        // prost writes messages without a package to `_.rs`.
+        code.extend(quote! {
+            tonic::include_proto!("_");
+        });
     }
-    Ok(())
+    let packages = packages
+        .iter()
+        .filter(|package| !package.is_empty())
+        .map(|package| package.split('.').collect())
+        .collect::<Vec<Vec<&str>>>();
+    code.extend(gen_package_mods(&packages, 0));
+    code
 }
 
-fn gen_tonic_mod(files: Vec<FileDescriptorProto>) -> TokenStream {
+/// Emits one nested `pub mod` per package segment at `depth`, with `include_proto!` for the
+/// packages ending there. `packages` must be sorted by their segments.
+fn gen_package_mods(packages: &[Vec<&str>], depth: usize) -> TokenStream {
     let mut modules = vec![];
-    for file in files {
-        //TODO: Use Literal::String here instead.
-        let package_ident = quote::format_ident!("{}", file.package());
-        let mod_str = format!("\"{}\"", package_ident);
-        let mod_token = TokenStream::from_str(&mod_str).unwrap();
+    for (segment, group) in &packages.iter().group_by(|segments| segments[depth]) {
+        let group = group.cloned().collect::<Vec<Vec<&str>>>();
+        let mod_ident = quote::format_ident!("{}", to_snake(segment));
+        let include = group.iter().find(|segments| segments.len() == depth + 1).map(|segments| {
+            let package = Literal::string(&segments.join("."));
+            quote! {
+                tonic::include_proto!(#package);
+            }
+        });
+        let nested = group
+            .into_iter()
+            .filter(|segments| segments.len() > depth + 1)
+            .collect::<Vec<Vec<&str>>>();
+        let nested_code = gen_package_mods(&nested, depth + 1);
         modules.push(quote! {
-            pub mod #package_ident {
-                tonic::include_proto!(#mod_token);
+            pub mod #mod_ident {
+                #include
+                #nested_code
             }
         });
     }
@@ -118,16 +186,68 @@ fn gen_tonic_mod(files: Vec<FileDescriptorProto>) -> TokenStream {
     code
 }
 
+/// Module identifiers for each segment of a dotted package name.
+fn package_segments(package: &str) -> Vec<Ident> {
+    package
+        .split('.')
+        .filter(|segment| !segment.is_empty())
+        .map(|segment| quote::format_ident!("{}", to_snake(segment)))
+        .collect()
+}
 
-fn apply_rustfmt(file: impl AsRef<OsStr>, edition: &str) -> Result<()> {
+fn apply_rustfmt(file: &Path, edition: &str) -> Result<()> {
     let mut cmd = Command::new("rustfmt");
     cmd.arg("--edition").arg(edition).arg(file);
-    cmd.status()?;
+    let output = cmd.output().map_err(|e| Error::Rustfmt {
+        path: file.to_path_buf(),
+        message: e.to_string(),
+    })?;
+    if !output.status.success() {
+        return Err(Error::Rustfmt {
+            path: file.to_path_buf(),
+            message: String::from_utf8_lossy(&output.stderr).into_owned(),
+        });
+    }
     Ok(())
 }
 
+/// Names of `protos` as `protoc` records them in the descriptor set, i.e. relative to the include
+/// path they were found in.
+fn proto_file_names(protos: &[impl AsRef<Path>], includes: &[impl AsRef<Path>]) -> Vec<String> {
+    protos
+        .iter()
+        .map(|proto| {
+            let proto = proto.as_ref();
+            let relative = relative_to_includes(proto, includes)
+                .or_else(|| relative_to_includes(&proto.canonicalize().ok()?, &canonical_includes(includes)))
+                .unwrap_or_else(|| proto.to_path_buf());
+            relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .join("/")
+        })
+        .collect()
+}
+
+fn relative_to_includes(proto: &Path, includes: &[impl AsRef<Path>]) -> Option<PathBuf> {
+    includes
+        .iter()
+        .find_map(|include| proto.strip_prefix(include.as_ref()).ok())
+        .map(Path::to_path_buf)
+}
+
+fn canonical_includes(includes: &[impl AsRef<Path>]) -> Vec<PathBuf> {
+    includes
+        .iter()
+        .filter_map(|include| include.as_ref().canonicalize().ok())
+        .collect()
+}
+
 fn gen_file_descriptor(protos: &[impl AsRef<Path>], includes: &[impl AsRef<Path>]) -> Result<FileDescriptorSet> {
-    let tmp = tempfile::Builder::new().prefix("prost-build").tempdir()?;
+    let tmp = tempfile::Builder::new().prefix("prost-build").tempdir().map_err(|source| Error::Io {
+        path: std::env::temp_dir(),
+        source,
+    })?;
     let file_descriptor_set_path = tmp.path().join("prost-descriptor-set");
 
     let mut cmd = Command::new(protoc());
@@ -143,29 +263,162 @@ fn gen_file_descriptor(protos: &[impl AsRef<Path>], includes: &[impl AsRef<Path>
         cmd.arg(proto.as_ref());
     }
 
-    cmd.status()?;
+    let output = cmd.output().map_err(Error::ProtocSpawn)?;
+    if !output.status.success() {
+        let command = format!("{:?}", cmd).replace('"', "");
+        return Err(Error::Protoc(ProtocError {
+            command,
+            includes: includes.iter().map(|include| include.as_ref().to_path_buf()).collect(),
+            status: output.status,
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        }));
+    }
 
-    let buf = fs::read(file_descriptor_set_path)?;
-    tmp.close()?;
-    Ok(FileDescriptorSet::decode(&*buf)?)
+    let buf = read_file(&file_descriptor_set_path)?;
+    let tmp_path = tmp.path().to_path_buf();
+    tmp.close().map_err(|source| Error::Io { path: tmp_path, source })?;
+    FileDescriptorSet::decode(&*buf).map_err(Error::Decode)
 }
 
-fn get_message_type(package: &str, pb_ident: &str) -> String {
-    let mut local_path = package.split('.').peekable();
+fn read_file(path: &Path) -> Result<Vec<u8>> {
+    fs::read(path).map_err(|source| Error::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
 
-    let mut ident_path = pb_ident[1..].split('.');
-    let ident_type = ident_path.next_back().unwrap();
-    let mut ident_path = ident_path.peekable();
+/// Resolves fully qualified protobuf type names to Rust type paths relative to the root of the
+/// generated file, where every package is a (nested) module emitted by `gen_tonic_mod`.
+struct TypeResolver {
+    packages: Vec<String>,
+    messages: HashSet<String>,
+    /// Package and imports of each file, by file name.
+    files: HashMap<String, (String, Vec<String>)>,
+}
 
-    // Skip path elements in common.
-    while local_path.peek().is_some() && local_path.peek() == ident_path.peek() {
-        local_path.next();
-        ident_path.next();
+impl TypeResolver {
+    /// Creates a resolver for every package in `files`, which should include the dependencies.
+    fn new(files: &[FileDescriptorProto]) -> Self {
+        let packages = files
+            .iter()
+            .map(|file| file.package().to_string())
+            .unique()
+            .sorted_by(|a, b| b.len().cmp(&a.len()))
+            .collect();
+        let mut messages = HashSet::new();
+        for file in files {
+            let prefix = if file.package().is_empty() {
+                String::new()
+            } else {
+                format!(".{}", file.package())
+            };
+            collect_messages(&prefix, &file.message_type, &mut messages);
+        }
+        let files = files
+            .iter()
+            .map(|file| (file.name().to_string(), (file.package().to_string(), file.dependency.clone())))
+            .collect();
+        TypeResolver { packages, messages, files }
     }
 
-    local_path
-        .map(|_| "super".to_string())
-        .chain(ident_path.map(to_snake))
-        .chain(std::iter::once(to_upper_camel(ident_type)))
-        .join("::")
+    /// Fails with [`Error::Unsupported`] if a request or response type of `files` is not part of
+    /// the descriptor set, e.g. because it was built without `--include_imports`.
+    fn check_defined(&self, files: &[FileDescriptorProto]) -> Result<()> {
+        for file in files {
+            for service in &file.service {
+                for method in &service.method {
+                    for pb_ident in &[method.input_type(), method.output_type()] {
+                        if !pb_ident.starts_with(".google.protobuf.") && !self.messages.contains(*pb_ident) {
+                            return Err(Error::Unsupported {
+                                file: file.name().to_string(),
+                                service: Some(service.name().to_string()),
+                                method: Some(method.name().to_string()),
+                                reason: format!("message type `{}` is not defined in the descriptor set", pb_ident),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Rust path of the type `pb_ident`, e.g. `.acme.billing.v1.Invoice.Line` resolves to
+    /// `acme::billing::v1::invoice::Line`.
+    fn resolve(&self, pb_ident: &str) -> TokenStream {
+        let pb_ident = pb_ident.trim_start_matches('.');
+        if let Some(well_known) = pb_ident.strip_prefix("google.protobuf.") {
+            return well_known_type(well_known);
+        }
+
+        let package = self.package_of(pb_ident);
+        let mut ident_path = pb_ident[if package.is_empty() { 0 } else { package.len() + 1 }..].split('.');
+        let ident_type = quote::format_ident!("{}", to_upper_camel(ident_path.next_back().unwrap()));
+        let package_segments = package_segments(package);
+        let message_segments = ident_path.map(|segment| quote::format_ident!("{}", to_snake(segment)));
+
+        quote! { #(#package_segments::)* #(#message_segments::)* #ident_type }
+    }
+
+    /// Packages whose modules the generated code for `files` refers to: the packages of the files
+    /// themselves and of every file they import, directly or not, as prost's modules refer to the
+    /// field types of their messages. `google.protobuf` is left out, it maps to `prost_types`.
+    fn required_packages(&self, files: &[FileDescriptorProto]) -> Vec<String> {
+        let mut packages = files.iter().map(|file| file.package()).collect::<Vec<&str>>();
+        let mut visited = HashSet::new();
+        let mut pending = files.iter().flat_map(|file| file.dependency.iter()).collect::<Vec<&String>>();
+        while let Some(name) = pending.pop() {
+            if !visited.insert(name) {
+                continue;
+            }
+            if let Some((package, dependencies)) = self.files.get(name) {
+                packages.push(package);
+                pending.extend(dependencies);
+            }
+        }
+        packages
+            .into_iter()
+            .filter(|package| *package != "google.protobuf")
+            .unique()
+            .map(String::from)
+            .collect()
+    }
+
+    /// The longest known package that prefixes the type name is the package it lives in, the
+    /// remaining segments are the (possibly nested) message names.
+    fn package_of<'a>(&'a self, pb_ident: &str) -> &'a str {
+        self.packages
+            .iter()
+            .find(|package| !package.is_empty() && pb_ident.starts_with(&format!("{}.", package)))
+            .map(String::as_str)
+            .unwrap_or("")
+    }
+}
+
+fn collect_messages(prefix: &str, messages: &[DescriptorProto], names: &mut HashSet<String>) {
+    for message in messages {
+        let name = format!("{}.{}", prefix, message.name());
+        collect_messages(&name, &message.nested_type, names);
+        names.insert(name);
+    }
+}
+
+/// Maps a `google.protobuf` type to the Rust type prost generates for it.
+fn well_known_type(name: &str) -> TokenStream {
+    match name {
+        "BoolValue" => quote! { bool },
+        "BytesValue" => quote! { ::prost::alloc::vec::Vec<u8> },
+        "DoubleValue" => quote! { f64 },
+        "Empty" => quote! { () },
+        "FloatValue" => quote! { f32 },
+        "Int32Value" => quote! { i32 },
+        "Int64Value" => quote! { i64 },
+        "StringValue" => quote! { ::prost::alloc::string::String },
+        "UInt32Value" => quote! { u32 },
+        "UInt64Value" => quote! { u64 },
+        _ => {
+            let ident = quote::format_ident!("{}", to_upper_camel(name));
+            quote! { ::prost_types::#ident }
+        }
+    }
 }
@@ -0,0 +1,334 @@
+use prost_types::FileDescriptorProto;
+use quote::quote;
+
+use proc_macro2::{Ident, Literal, TokenStream};
+
+use crate::{common::generate_error_definitions, Config, FileProtoInfo, MethodProtoInfo, ServiceProtoInfo, TypeResolver};
+
+pub(crate) fn generate_grpc_server_impl(files: Vec<FileDescriptorProto>, resolver: &TypeResolver, config: &Config) -> String {
+    let use_statements = generate_use_statements();
+    let error_definitions = generate_error_definitions(config);
+    let static_value_struct = generate_static_value_struct();
+    let grpc_server_handler = generate_grpc_server_handler();
+    let server_handler_methods = generate_grpc_server_handler_methods(files.clone());
+    let tonic_server_impls = generate_tonic_server_impls(files, resolver, config);
+    let code = quote! {
+        #use_statements
+        #error_definitions
+        #static_value_struct
+        #grpc_server_handler
+        #server_handler_methods
+        #tonic_server_impls
+    };
+
+    let formatted_code = format!("{}", code);
+    formatted_code
+}
+
+fn generate_use_statements() -> TokenStream {
+    quote! {
+        use async_std::prelude::StreamExt;
+        use value_trait::ValueAccess;
+    }
+}
+
+fn generate_static_value_struct() -> TokenStream {
+    quote! {
+        struct StaticValue(tremor_value::Value<'static>);
+    }
+}
+
+fn generate_grpc_server_handler() -> TokenStream {
+    quote! {
+        /// Forwards every call to the pipeline as events with `request_path` and `request_id` meta
+        /// fields. Client streams send one event per message, followed by a null event with `flag`
+        /// set once the stream ends, other calls send a single event with `flag` set. If a client
+        /// stream fails, its last event also has the `grpc_status` and `grpc_message` of the error.
+        ///
+        /// Replies go back through `send_reply` with the `request_id` of the call. Unary and client
+        /// streaming calls answer with the first reply, streaming responses send every reply until
+        /// one has `flag` set. A null reply with `flag` set ends the call without a message. Replies
+        /// to calls that have ended are ignored.
+        #[derive(Debug, Clone)]
+        pub struct GrpcServerHandler {
+            request_tx: async_std::channel::Sender<tremor_script::EventPayload>,
+            pending: std::sync::Arc<std::sync::Mutex<PendingMap>>,
+            next_request_id: std::sync::Arc<std::sync::atomic::AtomicU64>
+        }
+
+        type PendingMap = hashbrown::HashMap<u64, async_std::channel::Sender<StaticValue>>;
+
+        // Removes the pending entry of a call once the call is dropped, e.g. because it failed or the
+        // client went away, so replies to it are ignored.
+        struct PendingGuard {
+            pending: std::sync::Arc<std::sync::Mutex<PendingMap>>,
+            request_id: u64
+        }
+
+        impl Drop for PendingGuard {
+            fn drop(&mut self) {
+                lock_pending(&self.pending).remove(&self.request_id);
+            }
+        }
+
+        // The map stays consistent even if a panic poisoned the lock.
+        fn lock_pending(pending: &std::sync::Mutex<PendingMap>) -> std::sync::MutexGuard<'_, PendingMap> {
+            pending.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+        }
+    }
+}
+
+fn generate_grpc_server_handler_methods(files: Vec<FileDescriptorProto>) -> TokenStream {
+    let serve_code = generate_grpc_server_handler_serve(files);
+    quote! {
+        impl GrpcServerHandler {
+            pub fn new(request_tx: async_std::channel::Sender<tremor_script::EventPayload>) -> Self {
+                GrpcServerHandler {
+                    request_tx,
+                    pending: std::sync::Arc::new(std::sync::Mutex::new(hashbrown::HashMap::new())),
+                    next_request_id: std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0))
+                }
+            }
+
+            #serve_code
+
+            pub async fn send_reply(&self, event: tremor_pipeline::Event) -> Result<()> {
+                for (value, meta) in event.value_meta_iter() {
+                    if let Some(request_id) = meta.get_u64("request_id") {
+                        let flag = meta.contains_key("flag");
+                        let tx = if flag {
+                            lock_pending(&self.pending).remove(&request_id)
+                        } else {
+                            lock_pending(&self.pending).get(&request_id).cloned()
+                        };
+                        if let Some(tx) = tx {
+                            // A null reply with `flag` set only ends the response stream.
+                            let is_null = matches!(value, tremor_value::Value::Static(value_trait::StaticNode::Null));
+                            if !(flag && is_null) {
+                                tx.send(StaticValue(value.clone_static())).await.map_err(into_error)?;
+                            }
+                            if flag {
+                                tx.close();
+                            }
+                        }
+                    }
+                }
+                Ok(())
+            }
+
+            fn register(&self) -> (PendingGuard, async_std::channel::Receiver<StaticValue>) {
+                let request_id = self.next_request_id.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                let (tx, rx) = async_std::channel::unbounded::<StaticValue>();
+                lock_pending(&self.pending).insert(request_id, tx);
+                let guard = PendingGuard {
+                    pending: self.pending.clone(),
+                    request_id
+                };
+                (guard, rx)
+            }
+
+            async fn forward(
+                &self,
+                request_path: &'static str,
+                request_id: u64,
+                value: tremor_value::Value<'static>,
+                flag: bool,
+                error: Option<&tonic::Status>
+            ) -> std::result::Result<(), tonic::Status> {
+                let mut meta = tremor_value::value::Object::with_capacity(5);
+                meta.insert("request_path".into(), request_path.into());
+                meta.insert("request_id".into(), request_id.into());
+                if flag {
+                    meta.insert("flag".into(), true.into());
+                }
+                if let Some(status) = error {
+                    meta.insert("grpc_status".into(), (status.code() as i32).into());
+                    meta.insert("grpc_message".into(), status.message().to_string().into());
+                }
+                let event: tremor_script::EventPayload = (value, meta).into();
+                self.request_tx.send(event).await.map_err(status_from)
+            }
+
+            // Forwards the messages of a client stream and the null event with `flag` set that ends
+            // it. Returns the error the stream failed with, if any.
+            async fn forward_stream<T: serde::Serialize>(
+                &self,
+                request_path: &'static str,
+                request_id: u64,
+                stream: &mut tonic::Streaming<T>
+            ) -> std::result::Result<(), tonic::Status> {
+                let null = tremor_value::Value::Static(value_trait::StaticNode::Null);
+                let status = loop {
+                    let item = match stream.message().await {
+                        Ok(Some(item)) => item,
+                        Ok(None) => return self.forward(request_path, request_id, null, true, None).await,
+                        Err(status) => break status,
+                    };
+                    match tremor_value::to_value(item) {
+                        Ok(value) => self.forward(request_path, request_id, value, false, None).await?,
+                        Err(e) => break status_from(e),
+                    }
+                };
+                self.forward(request_path, request_id, null, true, Some(&status)).await?;
+                Err(status)
+            }
+        }
+
+        fn status_from<E: std::fmt::Display>(e: E) -> tonic::Status {
+            tonic::Status::internal(e.to_string())
+        }
+    }
+}
+
+fn generate_grpc_server_handler_serve(files: Vec<FileDescriptorProto>) -> TokenStream {
+    let mut services = vec![];
+    for file in files {
+        let package_path = file.package_path();
+        for service in &file.service {
+            let server_mod_ident = service.server_mod_ident();
+            let server_ident = service.server_ident();
+            services.push(quote! {
+                .add_service(#package_path #server_mod_ident::#server_ident::new(self.clone()))
+            });
+        }
+    }
+    if services.is_empty() {
+        return TokenStream::default();
+    }
+    let mut services_code = TokenStream::default();
+    services_code.extend(services);
+    quote! {
+        pub async fn serve(self, addr: std::net::SocketAddr) -> Result<()> {
+            tonic::transport::Server::builder()
+                #services_code
+                .serve(addr)
+                .await
+                .map_err(into_error)?;
+            Ok(())
+        }
+    }
+}
+
+fn generate_tonic_server_impls(files: Vec<FileDescriptorProto>, resolver: &TypeResolver, config: &Config) -> TokenStream {
+    let spawn = config.get_runtime().spawn();
+    let mut trait_impls = vec![];
+    for file in files {
+        let package_path = file.package_path();
+        for service in &file.service {
+            let server_mod_ident = service.server_mod_ident();
+            let trait_ident = service.trait_ident();
+            let mut methods_code = TokenStream::default();
+            for method in &service.method {
+                let request_path = Literal::string(&file.method_path(service, method));
+                let request_message_path = method.request_message_path(resolver);
+                let response_message_path = method.response_message_path(resolver);
+                let method_ident = method.name_ident();
+                let stream_type_ident = method.stream_type_ident();
+                let method_code = match (method.client_streaming(), method.server_streaming()) {
+                    (false, false) => generate_unary_handler(method_ident, request_path, request_message_path, response_message_path),
+                    (true, false) => generate_client_stream_handler(method_ident, request_path, request_message_path, response_message_path),
+                    (false, true) => generate_server_stream_handler(method_ident, stream_type_ident, request_path, request_message_path, response_message_path),
+                    (true, true) => generate_binary_stream_handler(spawn.clone(), method_ident, stream_type_ident, request_path, request_message_path, response_message_path),
+                };
+                methods_code.extend(method_code);
+            }
+            trait_impls.push(quote! {
+                #[tonic::async_trait]
+                impl #package_path #server_mod_ident::#trait_ident for GrpcServerHandler {
+                    #methods_code
+                }
+            });
+        }
+    }
+    let mut code = TokenStream::default();
+    code.extend(trait_impls);
+    code
+}
+
+fn generate_unary_handler(method_ident: Ident, request_path: Literal, request_message_path: TokenStream, response_message_path: TokenStream) -> TokenStream {
+    quote! {
+        async fn #method_ident(
+            &self,
+            request: tonic::Request<#request_message_path>
+        ) -> std::result::Result<tonic::Response<#response_message_path>, tonic::Status> {
+            let (guard, mut rx) = self.register();
+            let request_id = guard.request_id;
+            let value = tremor_value::to_value(request.into_inner()).map_err(status_from)?;
+            self.forward(#request_path, request_id, value, true, None).await?;
+            let reply = rx.next().await;
+            drop(guard);
+            let reply = reply.ok_or_else(|| tonic::Status::unavailable("no reply from pipeline"))?;
+            let body: #response_message_path = tremor_value::structurize(reply.0).map_err(status_from)?;
+            Ok(tonic::Response::new(body))
+        }
+    }
+}
+
+fn generate_client_stream_handler(method_ident: Ident, request_path: Literal, request_message_path: TokenStream, response_message_path: TokenStream) -> TokenStream {
+    quote! {
+        async fn #method_ident(
+            &self,
+            request: tonic::Request<tonic::Streaming<#request_message_path>>
+        ) -> std::result::Result<tonic::Response<#response_message_path>, tonic::Status> {
+            let (guard, mut rx) = self.register();
+            let request_id = guard.request_id;
+            let mut stream = request.into_inner();
+            self.forward_stream(#request_path, request_id, &mut stream).await?;
+            let reply = rx.next().await;
+            drop(guard);
+            let reply = reply.ok_or_else(|| tonic::Status::unavailable("no reply from pipeline"))?;
+            let body: #response_message_path = tremor_value::structurize(reply.0).map_err(status_from)?;
+            Ok(tonic::Response::new(body))
+        }
+    }
+}
+
+fn generate_server_stream_handler(method_ident: Ident, stream_type_ident: Ident, request_path: Literal, request_message_path: TokenStream, response_message_path: TokenStream) -> TokenStream {
+    quote! {
+        type #stream_type_ident = std::pin::Pin<Box<dyn async_std::stream::Stream<Item = std::result::Result<#response_message_path, tonic::Status>> + Send + Sync + 'static>>;
+
+        async fn #method_ident(
+            &self,
+            request: tonic::Request<#request_message_path>
+        ) -> std::result::Result<tonic::Response<Self::#stream_type_ident>, tonic::Status> {
+            let (guard, rx) = self.register();
+            let request_id = guard.request_id;
+            let value = tremor_value::to_value(request.into_inner()).map_err(status_from)?;
+            self.forward(#request_path, request_id, value, true, None).await?;
+            // The entry lives as long as the response stream, tonic drops it when the client goes away.
+            let stream = rx.map(move |reply| {
+                let _guard = &guard;
+                let body: std::result::Result<#response_message_path, tonic::Status> = tremor_value::structurize(reply.0).map_err(status_from);
+                body
+            });
+            Ok(tonic::Response::new(Box::pin(stream)))
+        }
+    }
+}
+
+fn generate_binary_stream_handler(spawn: TokenStream, method_ident: Ident, stream_type_ident: Ident, request_path: Literal, request_message_path: TokenStream, response_message_path: TokenStream) -> TokenStream {
+    quote! {
+        type #stream_type_ident = std::pin::Pin<Box<dyn async_std::stream::Stream<Item = std::result::Result<#response_message_path, tonic::Status>> + Send + Sync + 'static>>;
+
+        async fn #method_ident(
+            &self,
+            request: tonic::Request<tonic::Streaming<#request_message_path>>
+        ) -> std::result::Result<tonic::Response<Self::#stream_type_ident>, tonic::Status> {
+            let (guard, rx) = self.register();
+            let request_id = guard.request_id;
+            let mut stream = request.into_inner();
+            let handler = self.clone();
+            #spawn(async move {
+                // A failed request stream reaches the pipeline as an end event with its status.
+                let _ = handler.forward_stream(#request_path, request_id, &mut stream).await;
+            });
+            // The entry lives as long as the response stream, tonic drops it when the client goes away.
+            let stream = rx.map(move |reply| {
+                let _guard = &guard;
+                let body: std::result::Result<#response_message_path, tonic::Status> = tremor_value::structurize(reply.0).map_err(status_from);
+                body
+            });
+            Ok(tonic::Response::new(Box::pin(stream)))
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! Runs the `gen-tonic-impls` binary against the protos in `tests/fixtures`, checking its output
+//! against the committed snapshots in `tests/snapshots`.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+const ORDERS: &str = "tests/fixtures/acme/orders/v1/orders.proto";
+const ORDERS_SNAPSHOT: &str = "tests/snapshots/field_imports";
+
+fn gen_tonic_impls(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_gen-tonic-impls"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Asserts the exit code of a run and returns its stderr.
+fn assert_exit(output: Output, code: i32) -> String {
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(code), "{}", stderr);
+    stderr
+}
+
+fn assert_usage_error(args: &[&str], message: &str) {
+    let stderr = assert_exit(gen_tonic_impls(args), 2);
+    assert!(stderr.starts_with(&format!("error: {}\n", message)), "{}", stderr);
+}
+
+#[test]
+fn out_dir_is_required() {
+    assert_usage_error(&["--proto", ORDERS, "-I", "tests/fixtures"], "`--out-dir` is required");
+}
+
+#[test]
+fn proto_or_descriptor_set_is_required() {
+    assert_usage_error(
+        &["--out-dir", ORDERS_SNAPSHOT],
+        "either `--proto` or `--descriptor-set` is required",
+    );
+}
+
+#[test]
+fn proto_and_descriptor_set_are_mutually_exclusive() {
+    assert_usage_error(
+        &["--out-dir", ORDERS_SNAPSHOT, "--proto", ORDERS, "--descriptor-set", "orders.bin"],
+        "`--proto` and `--descriptor-set` are mutually exclusive",
+    );
+}
+
+#[test]
+fn include_and_descriptor_set_are_mutually_exclusive() {
+    assert_usage_error(
+        &["--out-dir", ORDERS_SNAPSHOT, "--descriptor-set", "orders.bin", "-Itests/fixtures"],
+        "`-I` and `--descriptor-set` are mutually exclusive",
+    );
+}
+
+#[test]
+fn options_need_a_value() {
+    assert_usage_error(&["--proto", ORDERS, "--out-dir"], "`--out-dir` requires a value");
+}
+
+#[test]
+fn include_dirs_can_be_attached_or_separate() {
+    let check = ["--check", "--out-dir", ORDERS_SNAPSHOT, "--proto", ORDERS];
+    let includes: &[&[&str]] = &[&["-Itests/fixtures"], &["-I", "tests/fixtures"], &["--include", "tests/fixtures"]];
+    for include in includes {
+        assert_exit(gen_tonic_impls(&[&check[..], include].concat()), 0);
+    }
+    // Without the include dir protoc can't resolve the imports of the proto.
+    assert_exit(gen_tonic_impls(&check), 1);
+}
+
+/// Runs `--check` over `out_dir` and returns the files it reports as out of date.
+fn outdated_files(out_dir: &Path) -> Vec<PathBuf> {
+    let out_dir = out_dir.to_str().unwrap();
+    let output = gen_tonic_impls(&["--check", "--out-dir", out_dir, "--proto", ORDERS, "-I", "tests/fixtures"]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let outdated = stderr
+        .lines()
+        .map(|line| PathBuf::from(line.strip_suffix(" is out of date").unwrap_or_else(|| panic!("{}", stderr))))
+        .collect::<Vec<_>>();
+    let code = if outdated.is_empty() { 0 } else { 1 };
+    assert_eq!(output.status.code(), Some(code), "{}", stderr);
+    outdated
+}
+
+#[test]
+fn check_reports_outdated_files() {
+    let out_dir = tempfile::tempdir().unwrap();
+    let client = out_dir.path().join("grpc_client.rs");
+    let server = out_dir.path().join("grpc_server.rs");
+    assert_eq!(outdated_files(out_dir.path()), vec![client.clone(), server.clone()]);
+
+    let out = out_dir.path().to_str().unwrap();
+    assert_exit(gen_tonic_impls(&["--out-dir", out, "--proto", ORDERS, "-I", "tests/fixtures"]), 0);
+    assert!(outdated_files(out_dir.path()).is_empty());
+    let snapshot = Path::new(env!("CARGO_MANIFEST_DIR")).join(ORDERS_SNAPSHOT);
+    assert_eq!(fs::read(&client).unwrap(), fs::read(snapshot.join("grpc_client.rs")).unwrap());
+
+    fs::write(&server, "").unwrap();
+    assert_eq!(outdated_files(out_dir.path()), vec![server]);
+    fs::remove_file(&client).unwrap();
+    assert_eq!(outdated_files(out_dir.path()).len(), 2);
+}
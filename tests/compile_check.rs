@@ -0,0 +1,269 @@
+//! Builds the generated bindings against real tonic.
+//!
+//! By default the `tremor-*` crates are replaced with the stand-ins in `tests/shims`, which only
+//! provide the API the bindings use. The tests in `tests/e2e` are run against the bindings in the
+//! scratch crate as well, they talk to a generated server over local sockets.
+//!
+//! The `*_tremor` variants check against the real crates instead, they need network access (or a
+//! warm cargo cache) and a tremor checkout, so they are ignored by default. Run them with
+//! `cargo test --test compile_check -- --ignored`. Set `GEN_TONIC_IMPLS_TREMOR` to a local
+//! `tremor-runtime` checkout to avoid fetching it from git.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use gen_tonic_impls::{Config, Runtime};
+
+const TREMOR_GIT: &str = "https://github.com/tremor-rs/tremor-runtime";
+const TREMOR_TAG: &str = "v0.11.4";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Tremor {
+    Shims,
+    Release,
+}
+
+fn tremor_dependency(tremor: Tremor, name: &str, dir: &str) -> String {
+    if tremor == Tremor::Shims {
+        let shim = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("shims").join(name);
+        return format!("{} = {{ path = {:?} }}\n", name, shim);
+    }
+    match env::var("GEN_TONIC_IMPLS_TREMOR") {
+        Ok(root) => format!("{} = {{ path = {:?} }}\n", name, Path::new(&root).join(dir)),
+        Err(_) => format!("{} = {{ git = {:?}, tag = {:?} }}\n", name, TREMOR_GIT, TREMOR_TAG),
+    }
+}
+
+fn manifest(name: &str, tremor: Tremor) -> String {
+    let mut manifest = format!(
+        r#"[package]
+name = "{}"
+version = "0.0.0"
+edition = "2018"
+
+[workspace]
+
+[lib]
+name = "generated"
+
+[dependencies]
+async-std = "1.10"
+async-trait = "0.1"
+base64 = "0.13"
+hashbrown = "0.11"
+prost = "0.8"
+prost-types = "0.8"
+serde = {{ version = "1", features = ["derive"] }}
+tokio = {{ version = "1", features = ["rt", "time"] }}
+tonic = {{ version = "0.5", features = ["tls"] }}
+value-trait = "0.2"
+"#,
+        name
+    );
+    manifest.push_str(&tremor_dependency(tremor, "tremor-value", "tremor-value"));
+    manifest.push_str(&tremor_dependency(tremor, "tremor-script", "tremor-script"));
+    manifest.push_str(&tremor_dependency(tremor, "tremor-pipeline", "tremor-pipeline"));
+    manifest.push_str("\n[build-dependencies]\ntonic-build = \"0.5\"\n");
+    manifest.push_str(
+        r#"
+[dev-dependencies]
+tokio = { version = "1", features = ["io-util", "macros", "net", "rt-multi-thread"] }
+tokio-stream = { version = "0.1", features = ["net"] }
+"#,
+    );
+    manifest
+}
+
+fn build_script(protos: &[PathBuf], include: &Path) -> String {
+    format!(
+        r##"fn main() {{
+    tonic_build::configure()
+        .type_attribute(".", "#[derive(serde::Serialize, serde::Deserialize)]")
+        .compile(&{:?}, &[{:?}])
+        .unwrap();
+}}
+"##,
+        protos, include
+    )
+}
+
+const LIB: &str = r#"#[path = "grpc_client.rs"]
+pub mod grpc_client;
+#[path = "grpc_server.rs"]
+pub mod grpc_server;
+"#;
+
+/// Generates the bindings for `protos` into a scratch crate and runs `cargo <command>` on it.
+fn cargo(command: &str, name: &str, protos: &[&str], include: &str, config: &mut Config, tremor: Tremor, tests: &[&str]) {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let include = root.join(include);
+    let protos = protos.iter().map(|proto| root.join(proto)).collect::<Vec<_>>();
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src");
+    fs::create_dir(&src).unwrap();
+    config.out_dir(&src).compile(&protos, &[&include]).unwrap();
+    fs::write(dir.path().join("Cargo.toml"), manifest(name, tremor)).unwrap();
+    fs::write(dir.path().join("build.rs"), build_script(&protos, &include)).unwrap();
+    fs::write(src.join("lib.rs"), LIB).unwrap();
+    if !tests.is_empty() {
+        fs::create_dir(dir.path().join("tests")).unwrap();
+    }
+    for test in tests {
+        let file = format!("{}.rs", test);
+        fs::copy(root.join("tests").join("e2e").join(&file), dir.path().join("tests").join(&file)).unwrap();
+    }
+
+    let status = Command::new(env::var("CARGO").unwrap_or_else(|_| String::from("cargo")))
+        .arg(command)
+        .arg("--manifest-path")
+        .arg(dir.path().join("Cargo.toml"))
+        .env("CARGO_TARGET_DIR", root.join("target").join("compile-check"))
+        .env("GEN_TONIC_IMPLS_FIXTURES", root.join("tests").join("fixtures"))
+        .status()
+        .unwrap();
+    assert!(status.success(), "`cargo {}` failed for the bindings of {:?}", command, protos);
+}
+
+/// Generates the bindings for `protos` into a scratch crate and runs `cargo check` on it.
+fn check(name: &str, protos: &[&str], include: &str, config: &mut Config, tremor: Tremor) {
+    cargo("check", name, protos, include, config, tremor, &[]);
+}
+
+/// Generates the bindings for `protos` into a scratch crate and runs `tests` from `tests/e2e` on it.
+fn run(name: &str, protos: &[&str], include: &str, config: &mut Config, tests: &[&str]) {
+    cargo("test", name, protos, include, config, Tremor::Shims, tests);
+}
+
+#[test]
+fn helloworld() {
+    check("compile-check-helloworld", &["helloworld.proto"], ".", &mut Config::new(), Tremor::Shims);
+}
+
+#[test]
+fn streaming_shapes() {
+    check(
+        "compile-check-shapes",
+        &["tests/fixtures/shapes.proto"],
+        "tests/fixtures",
+        &mut Config::new(),
+        Tremor::Shims,
+    );
+}
+
+#[test]
+fn nested_packages() {
+    check(
+        "compile-check-nested-packages",
+        &["tests/fixtures/acme/billing/v1/invoices.proto"],
+        "tests/fixtures",
+        &mut Config::new(),
+        Tremor::Shims,
+    );
+}
+
+#[test]
+fn field_imports() {
+    check(
+        "compile-check-field-imports",
+        &["tests/fixtures/acme/orders/v1/orders.proto"],
+        "tests/fixtures",
+        &mut Config::new(),
+        Tremor::Shims,
+    );
+}
+
+#[test]
+fn keyword_fields() {
+    check(
+        "compile-check-keyword-fields",
+        &["tests/fixtures/keywords.proto"],
+        "tests/fixtures",
+        &mut Config::new(),
+        Tremor::Shims,
+    );
+}
+
+#[test]
+fn multiple_services() {
+    check(
+        "compile-check-multiple-services",
+        &["tests/fixtures/multiple_services.proto"],
+        "tests/fixtures",
+        &mut Config::new(),
+        Tremor::Shims,
+    );
+}
+
+#[test]
+fn method_filter() {
+    check(
+        "compile-check-method-filter",
+        &["tests/fixtures/multiple_services.proto"],
+        "tests/fixtures",
+        Config::new().method("inventory.Items/Get"),
+        Tremor::Shims,
+    );
+}
+
+#[test]
+fn tokio_runtime() {
+    check(
+        "compile-check-tokio",
+        &["tests/fixtures/shapes.proto"],
+        "tests/fixtures",
+        Config::new().runtime(Runtime::Tokio),
+        Tremor::Shims,
+    );
+}
+
+#[test]
+fn tls() {
+    run(
+        "compile-check-tls",
+        &["helloworld.proto"],
+        ".",
+        Config::new().tls(true).runtime(Runtime::Tokio),
+        &["tls"],
+    );
+}
+
+#[test]
+fn shapes_calls() {
+    run(
+        "compile-check-shapes-calls",
+        &["tests/fixtures/shapes.proto"],
+        "tests/fixtures",
+        Config::new()
+            .runtime(Runtime::Tokio)
+            .concurrency_limit("shapes.Shapes/ListPoints", 1)
+            .stream_capacity("shapes.Shapes/RecordPoints", 2),
+        &["shapes"],
+    );
+}
+
+#[test]
+#[ignore]
+fn helloworld_tremor() {
+    check("compile-check-helloworld", &["helloworld.proto"], ".", &mut Config::new(), Tremor::Release);
+}
+
+#[test]
+#[ignore]
+fn streaming_shapes_tremor() {
+    check(
+        "compile-check-shapes",
+        &["tests/fixtures/shapes.proto"],
+        "tests/fixtures",
+        &mut Config::new(),
+        Tremor::Release,
+    );
+}
+
+#[test]
+#[ignore]
+fn tls_tremor() {
+    check("compile-check-tls", &["helloworld.proto"], ".", Config::new().tls(true), Tremor::Release);
+}
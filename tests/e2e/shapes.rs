@@ -0,0 +1,565 @@
+//! Runs calls through the generated shapes client against the generated server. `ListPoints` is
+//! limited to one call at a time, and `RecordPoints` streams queue up to two points.
+
+use std::time::Duration;
+
+use async_std::channel::{bounded, unbounded, Receiver, Sender};
+use generated::{
+    grpc_client::{EndpointConfig, Endpoints, GrpcClientHandler, RequestStatus},
+    grpc_server::{shapes::shapes_server::ShapesServer, GrpcServerHandler},
+};
+use tremor_pipeline::{Event, EventId};
+use tremor_script::EventPayload;
+use tremor_value::{value::Object, Value};
+use value_trait::{Value as _, ValueAccess};
+
+const OK: i64 = 0;
+const CANCELLED: i64 = 1;
+const DEADLINE_EXCEEDED: i64 = 4;
+const NOT_FOUND: i64 = 5;
+const PERMISSION_DENIED: i64 = 7;
+
+/// A `latitude` or `point_count` the test pipeline never answers.
+const UNANSWERED: i64 = -1;
+
+/// Serves the generated handler on a free local port. Every finished call is answered with a
+/// default summary or point, `ListPoints` with one point followed by a null end reply, unless its
+/// request is marked with `UNANSWERED`. Returns the uri and every event the handler forwards to the
+/// pipeline.
+async fn serve() -> (String, Receiver<EventPayload>) {
+    serve_with(Ok).await
+}
+
+/// Like `serve`, with `interceptor` run on every request.
+async fn serve_with<F>(interceptor: F) -> (String, Receiver<EventPayload>)
+where
+    F: FnMut(tonic::Request<()>) -> Result<tonic::Request<()>, tonic::Status>,
+    F: Clone + Send + 'static,
+{
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (request_tx, request_rx) = unbounded::<EventPayload>();
+    let (forwarded_tx, forwarded_rx) = unbounded::<EventPayload>();
+    let handler = GrpcServerHandler::new(request_tx);
+    tokio::spawn(
+        tonic::transport::Server::builder()
+            .add_service(ShapesServer::with_interceptor(handler.clone(), interceptor))
+            .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)),
+    );
+    tokio::spawn(async move {
+        while let Ok(request) = request_rx.recv().await {
+            let _ = forwarded_tx.send(request.clone()).await;
+            let meta = request.suffix().meta();
+            let value = request.suffix().value();
+            let unanswered = value.get_i64("latitude") == Some(UNANSWERED)
+                || value.get_i64("point_count") == Some(UNANSWERED);
+            if !meta.contains_key("flag") || unanswered {
+                continue;
+            }
+            let request_id = meta.get_u64("request_id").unwrap();
+            let mut message = Object::new();
+            if meta.get_str("request_path") == Some("shapes.Shapes/RecordPoints") {
+                message.insert("point_count".into(), 0.into());
+            } else {
+                message.insert("latitude".into(), 0.into());
+                message.insert("longitude".into(), 0.into());
+            }
+            if meta.get_str("request_path") == Some("shapes.Shapes/ListPoints") {
+                handler.send_reply(server_reply(request_id, message.into(), false)).await.unwrap();
+                handler.send_reply(server_reply(request_id, Value::default(), true)).await.unwrap();
+            } else {
+                handler.send_reply(server_reply(request_id, message.into(), true)).await.unwrap();
+            }
+        }
+    });
+    (format!("http://{}", addr), forwarded_rx)
+}
+
+/// Forwards connections to the server at `uri` until the returned sender is dropped, which cuts
+/// them all.
+async fn proxy(uri: &str) -> (String, Sender<()>) {
+    let target = uri.trim_start_matches("http://").to_string();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (cut, cut_rx) = bounded::<()>(1);
+    tokio::spawn(async move {
+        while let Ok((mut inbound, _)) = listener.accept().await {
+            let mut outbound = tokio::net::TcpStream::connect(&target).await.unwrap();
+            let cut_rx = cut_rx.clone();
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound) => {}
+                    _ = cut_rx.recv() => {}
+                }
+            });
+        }
+    });
+    (format!("http://{}", addr), cut)
+}
+
+fn server_reply(request_id: u64, value: Value<'static>, flag: bool) -> Event {
+    let mut meta = Object::new();
+    meta.insert("request_id".into(), request_id.into());
+    if flag {
+        meta.insert("flag".into(), true.into());
+    }
+    Event {
+        data: (value, meta).into(),
+        ..Event::default()
+    }
+}
+
+fn stream_event(event_id: u64, stream_id: u64, command: &'static str, value: Value<'static>) -> Event {
+    stream_event_for("shapes.Shapes/RecordPoints", event_id, stream_id, command, value)
+}
+
+fn stream_event_for(
+    request_path: &'static str,
+    event_id: u64,
+    stream_id: u64,
+    command: &'static str,
+    value: Value<'static>,
+) -> Event {
+    let mut meta = Object::new();
+    meta.insert("request_path".into(), request_path.into());
+    meta.insert("stream_id".into(), stream_id.into());
+    meta.insert("stream_command".into(), command.into());
+    Event {
+        id: EventId::new(0, 0, event_id),
+        data: (value, meta).into(),
+        ..Event::default()
+    }
+}
+
+fn point() -> Value<'static> {
+    let mut point = Object::new();
+    point.insert("latitude".into(), 1.into());
+    point.insert("longitude".into(), 2.into());
+    point.into()
+}
+
+fn unanswered_point() -> Value<'static> {
+    let mut point = Object::new();
+    point.insert("latitude".into(), UNANSWERED.into());
+    point.insert("longitude".into(), 0.into());
+    point.into()
+}
+
+async fn next_reply(reply_rx: &Receiver<EventPayload>) -> EventPayload {
+    tokio::time::timeout(Duration::from_secs(10), reply_rx.recv())
+        .await
+        .expect("no reply")
+        .unwrap()
+}
+
+fn connect(uri: String, reply_tx: Sender<EventPayload>) -> GrpcClientHandler {
+    let endpoints = Endpoints {
+        default: Some(EndpointConfig::new(uri)),
+        ..Endpoints::default()
+    };
+    GrpcClientHandler::connect(&endpoints, reply_tx).unwrap()
+}
+
+async fn reply(reply_rx: &Receiver<EventPayload>) -> (u64, Option<i64>) {
+    let reply = next_reply(reply_rx).await;
+    let meta = reply.suffix().meta();
+    (meta.get_u64("stream_id").unwrap(), meta.get_i64("grpc_status"))
+}
+
+/// A batch of `(value, meta)` pairs, laid out as `tremor_pipeline::Event::value_meta_iter` reads it.
+fn batch(events: Vec<(Value<'static>, Object<'static>)>) -> Event {
+    let events = events
+        .into_iter()
+        .map(|(value, meta)| {
+            let mut data = Object::new();
+            data.insert("value".into(), value);
+            data.insert("meta".into(), meta.into());
+            let mut element = Object::new();
+            element.insert("data".into(), data.into());
+            Value::from(element)
+        })
+        .collect::<Vec<_>>();
+    Event {
+        data: (events, Object::new()).into(),
+        is_batch: true,
+        ..Event::default()
+    }
+}
+
+/// Like `reply`, along with the method the reply belongs to.
+async fn method_reply(reply_rx: &Receiver<EventPayload>) -> (String, u64, Option<i64>) {
+    let reply = next_reply(reply_rx).await;
+    let meta = reply.suffix().meta();
+    let path = meta.get_str("request_path").unwrap().to_string();
+    (path, meta.get_u64("stream_id").unwrap(), meta.get_i64("grpc_status"))
+}
+
+/// The method and `grpc_status` of the next reply to a call without a stream.
+async fn unary_reply(reply_rx: &Receiver<EventPayload>) -> (String, Option<i64>) {
+    let reply = next_reply(reply_rx).await;
+    let meta = reply.suffix().meta();
+    (meta.get_str("request_path").unwrap().to_string(), meta.get_i64("grpc_status"))
+}
+
+/// Fails if anything is replied within a grace period.
+async fn no_reply(reply_rx: &Receiver<EventPayload>) {
+    if let Ok(reply) = tokio::time::timeout(Duration::from_millis(500), reply_rx.recv()).await {
+        panic!("unexpected reply {:?}", reply.unwrap().suffix().meta());
+    }
+}
+
+#[tokio::test]
+async fn half_close_does_not_open_streams() {
+    let (uri, _) = serve().await;
+    let (reply_tx, reply_rx) = unbounded();
+    let mut client = connect(uri, reply_tx);
+
+    let status = client.send_request(&stream_event(1, 7, "half_close", Value::default())).await.unwrap();
+    assert_eq!(status, RequestStatus::Sent);
+    assert_eq!(reply(&reply_rx).await, (7, Some(NOT_FOUND)));
+    no_reply(&reply_rx).await;
+
+    client.send_request(&stream_event(2, 8, "open", point())).await.unwrap();
+    client.send_request(&stream_event(3, 8, "cancel", Value::default())).await.unwrap();
+    assert_eq!(reply(&reply_rx).await, (8, Some(CANCELLED)));
+    client.send_request(&stream_event(4, 8, "half_close", point())).await.unwrap();
+    assert_eq!(reply(&reply_rx).await, (8, Some(NOT_FOUND)));
+    no_reply(&reply_rx).await;
+
+    client.send_request(&stream_event(5, 9, "open", point())).await.unwrap();
+    client.send_request(&stream_event(6, 9, "half_close", point())).await.unwrap();
+    assert_eq!(reply(&reply_rx).await, (9, Some(OK)));
+}
+
+#[tokio::test]
+async fn stream_ids_are_scoped_to_their_method() {
+    let (uri, _) = serve().await;
+    let (reply_tx, reply_rx) = unbounded();
+    let mut client = connect(uri, reply_tx);
+
+    let record = "shapes.Shapes/RecordPoints";
+    let chat = "shapes.Shapes/Chat";
+    client.send_request(&stream_event_for(record, 1, 1, "open", point())).await.unwrap();
+    client.send_request(&stream_event_for(chat, 2, 1, "open", point())).await.unwrap();
+    client.send_request(&stream_event_for(chat, 3, 1, "data", point())).await.unwrap();
+    no_reply(&reply_rx).await;
+
+    client.send_request(&stream_event_for(record, 4, 1, "half_close", point())).await.unwrap();
+    assert_eq!(method_reply(&reply_rx).await, (record.to_string(), 1, Some(OK)));
+    client.send_request(&stream_event_for(chat, 5, 1, "cancel", Value::default())).await.unwrap();
+    assert_eq!(method_reply(&reply_rx).await, (chat.to_string(), 1, Some(CANCELLED)));
+    no_reply(&reply_rx).await;
+}
+
+#[tokio::test]
+async fn batched_unary_calls() {
+    let (uri, _) = serve().await;
+    let (reply_tx, reply_rx) = unbounded();
+    let mut client = connect(uri, reply_tx);
+
+    let mut meta = Object::new();
+    meta.insert("request_path".into(), "shapes.Shapes/GetPoint".into());
+    let event = batch(vec![(point(), meta.clone()), (point(), meta)]);
+    assert_eq!(client.send_request(&event).await.unwrap(), RequestStatus::Sent);
+    for _ in 0..2 {
+        let (path, status) = unary_reply(&reply_rx).await;
+        assert_eq!((path.as_str(), status), ("shapes.Shapes/GetPoint", Some(OK)));
+    }
+    no_reply(&reply_rx).await;
+}
+
+/// Fails unless every sender of the reply channel, the spawned calls included, is gone.
+async fn reply_channel_closed(reply_rx: &Receiver<EventPayload>) {
+    let recv = tokio::time::timeout(Duration::from_secs(10), reply_rx.recv()).await;
+    assert!(recv.expect("a call is still running").is_err());
+}
+
+#[tokio::test]
+async fn drain_aborts_calls_blocked_on_a_full_reply_channel() {
+    let (uri, _) = serve().await;
+    let (reply_tx, reply_rx) = bounded(1);
+    reply_tx.send(EventPayload::default()).await.unwrap();
+    let mut client = connect(uri, reply_tx);
+
+    let mut meta = Object::new();
+    meta.insert("request_path".into(), "shapes.Shapes/GetPoint".into());
+    let event = Event {
+        data: (point(), meta).into(),
+        ..Event::default()
+    };
+    assert_eq!(client.send_request(&event).await.unwrap(), RequestStatus::Sent);
+
+    assert!(!client.drain(Duration::from_secs(1)).await);
+    reply_rx.recv().await.unwrap();
+    reply_channel_closed(&reply_rx).await;
+}
+
+#[tokio::test]
+async fn close_cancels_streams() {
+    let (uri, _) = serve().await;
+    let (reply_tx, reply_rx) = unbounded();
+    let mut client = connect(uri, reply_tx);
+
+    let event = stream_event_for("shapes.Shapes/Chat", 1, 3, "open", point());
+    client.send_request(&event).await.unwrap();
+    no_reply(&reply_rx).await;
+
+    client.close();
+    assert_eq!(reply(&reply_rx).await, (3, Some(CANCELLED)));
+    reply_channel_closed(&reply_rx).await;
+}
+
+#[tokio::test]
+async fn null_replies_end_server_streams() {
+    let (uri, _) = serve().await;
+    let (reply_tx, reply_rx) = unbounded();
+    let mut client = connect(uri, reply_tx);
+
+    let mut meta = Object::new();
+    meta.insert("request_path".into(), "shapes.Shapes/ListPoints".into());
+    let mut summary = Object::new();
+    summary.insert("point_count".into(), 1.into());
+    let event = Event {
+        data: (summary, meta).into(),
+        ..Event::default()
+    };
+    client.send_request(&event).await.unwrap();
+    let message = next_reply(&reply_rx).await;
+    assert_eq!(message.suffix().meta().get_i64("grpc_status"), Some(OK));
+    assert_eq!(message.suffix().value().get_i64("latitude"), Some(0));
+    let end = next_reply(&reply_rx).await;
+    assert_eq!(end.suffix().meta().get_i64("grpc_status"), Some(OK));
+    assert_eq!(end.suffix().meta().get_bool("end_of_stream"), Some(true));
+    no_reply(&reply_rx).await;
+}
+
+#[tokio::test]
+async fn broken_request_streams_end_with_their_status() {
+    let (uri, forwarded_rx) = serve().await;
+    let (uri, cut) = proxy(&uri).await;
+    let (reply_tx, _reply_rx) = unbounded();
+    let mut client = connect(uri, reply_tx);
+
+    let chat = "shapes.Shapes/Chat";
+    client.send_request(&stream_event_for(chat, 1, 1, "open", point())).await.unwrap();
+    let message = next_reply(&forwarded_rx).await;
+    assert!(!message.suffix().meta().contains_key("flag"));
+    drop(cut);
+
+    let end = next_reply(&forwarded_rx).await;
+    let meta = end.suffix().meta();
+    assert_eq!(meta.get_bool("flag"), Some(true));
+    assert!(end.suffix().value().is_null());
+    assert_ne!(meta.get_i64("grpc_status").unwrap_or(OK), OK);
+}
+
+#[tokio::test]
+async fn headers_reach_the_server_as_metadata() {
+    let (metadata_tx, metadata_rx) = unbounded();
+    let (uri, _) = serve_with(move |request: tonic::Request<()>| {
+        let _ = metadata_tx.try_send(request.metadata().clone());
+        Ok(request)
+    })
+    .await;
+    let (reply_tx, reply_rx) = unbounded();
+    let mut client = connect(uri, reply_tx);
+
+    let mut headers = Object::new();
+    headers.insert("x-tenant".into(), "acme".into());
+    headers.insert("x-tags".into(), vec![Value::from("a"), Value::from("b")].into());
+    headers.insert("trace-bin".into(), base64::encode([0u8, 1, 255]).into());
+    let mut meta = Object::new();
+    meta.insert("request_path".into(), "shapes.Shapes/GetPoint".into());
+    meta.insert("headers".into(), headers.into());
+    let event = Event {
+        data: (point(), meta).into(),
+        ..Event::default()
+    };
+    client.send_request(&event).await.unwrap();
+    assert_eq!(unary_reply(&reply_rx).await.1, Some(OK));
+
+    let metadata = metadata_rx.recv().await.unwrap();
+    assert_eq!(metadata.get("x-tenant").unwrap(), "acme");
+    let tags = metadata.get_all("x-tags").iter().map(|tag| tag.to_str().unwrap()).collect::<Vec<_>>();
+    assert_eq!(tags, vec!["a", "b"]);
+    let trace = metadata.get_bin("trace-bin").unwrap().to_bytes().unwrap();
+    assert_eq!(trace.as_ref(), &[0u8, 1, 255]);
+}
+
+#[tokio::test]
+async fn replies_carry_the_request_path_correlation_headers_and_status() {
+    let (uri, _) = serve_with(|request: tonic::Request<()>| {
+        if !request.metadata().contains_key("x-deny") {
+            return Ok(request);
+        }
+        let mut trailers = tonic::metadata::MetadataMap::new();
+        trailers.insert("x-reason", "testing".parse().unwrap());
+        Err(tonic::Status::with_metadata(tonic::Code::PermissionDenied, "denied", trailers))
+    })
+    .await;
+    let (reply_tx, reply_rx) = unbounded();
+    let mut client = connect(uri, reply_tx);
+
+    let mut correlation = Object::new();
+    correlation.insert("order".into(), 42.into());
+    let mut meta = Object::new();
+    meta.insert("request_path".into(), "shapes.Shapes/GetPoint".into());
+    meta.insert("correlation".into(), correlation.into());
+    let event = Event {
+        data: (point(), meta.clone()).into(),
+        ..Event::default()
+    };
+    client.send_request(&event).await.unwrap();
+    let reply = next_reply(&reply_rx).await;
+    let reply_meta = reply.suffix().meta();
+    assert_eq!(reply_meta.get_str("request_path"), Some("shapes.Shapes/GetPoint"));
+    assert_eq!(reply_meta.get("correlation").and_then(|c| c.get_u64("order")), Some(42));
+    assert_eq!(reply_meta.get_i64("grpc_status"), Some(OK));
+    assert_eq!(reply_meta.get_str("grpc_message"), Some(""));
+    let headers = reply_meta.get("headers").unwrap();
+    assert_eq!(headers.get_str("content-type"), Some("application/grpc"));
+
+    let mut headers = Object::new();
+    headers.insert("x-deny".into(), "yes".into());
+    meta.insert("headers".into(), headers.into());
+    let event = Event {
+        data: (point(), meta).into(),
+        ..Event::default()
+    };
+    client.send_request(&event).await.unwrap();
+    let reply = next_reply(&reply_rx).await;
+    let reply_meta = reply.suffix().meta();
+    assert!(reply.suffix().value().is_null());
+    assert_eq!(reply_meta.get("correlation").and_then(|c| c.get_u64("order")), Some(42));
+    assert_eq!(reply_meta.get_i64("grpc_status"), Some(PERMISSION_DENIED));
+    assert_eq!(reply_meta.get_str("grpc_message"), Some("denied"));
+    let trailers = reply_meta.get("trailers").unwrap();
+    assert_eq!(trailers.get_str("x-reason"), Some("testing"));
+}
+
+#[tokio::test]
+async fn replies_carry_the_event_id_and_sequence() {
+    let (uri, _) = serve().await;
+    let (reply_tx, reply_rx) = unbounded();
+    let mut client = connect(uri, reply_tx);
+
+    let mut meta = Object::new();
+    meta.insert("request_path".into(), "shapes.Shapes/ListPoints".into());
+    let mut summary = Object::new();
+    summary.insert("point_count".into(), 1.into());
+    let event = Event {
+        id: EventId::new(1, 2, 3),
+        data: (summary, meta).into(),
+        ..Event::default()
+    };
+    client.send_request(&event).await.unwrap();
+    for sequence in 0..2 {
+        let reply = next_reply(&reply_rx).await;
+        let meta = reply.suffix().meta();
+        let event_id = meta.get("event_id").unwrap();
+        assert_eq!(event_id.get_u64("source_id"), Some(1));
+        assert_eq!(event_id.get_u64("stream_id"), Some(2));
+        assert_eq!(event_id.get_u64("event_id"), Some(3));
+        assert_eq!(meta.get_u64("sequence"), Some(sequence));
+        assert_eq!(meta.get_bool("end_of_stream"), if sequence == 1 { Some(true) } else { None });
+    }
+
+    client.send_request(&stream_event(4, 5, "open", point())).await.unwrap();
+    client.send_request(&stream_event(6, 5, "half_close", point())).await.unwrap();
+    let reply = next_reply(&reply_rx).await;
+    let meta = reply.suffix().meta();
+    assert_eq!(meta.get("event_id").and_then(|id| id.get_u64("event_id")), Some(4));
+    assert_eq!(meta.get_u64("stream_id"), Some(5));
+    assert_eq!(meta.get_u64("sequence"), Some(0));
+    no_reply(&reply_rx).await;
+}
+
+#[tokio::test]
+async fn timeouts_and_deadlines_end_calls() {
+    let (uri, _) = serve().await;
+    let (reply_tx, reply_rx) = unbounded();
+    let mut client = connect(uri, reply_tx);
+
+    let call = |value: Value<'static>, key: &str, limit: u64| {
+        let mut meta = Object::new();
+        meta.insert("request_path".into(), "shapes.Shapes/GetPoint".into());
+        meta.insert(key.to_string().into(), limit.into());
+        Event {
+            data: (value, meta).into(),
+            ..Event::default()
+        }
+    };
+    client.send_request(&call(point(), "timeout_ms", 10_000)).await.unwrap();
+    assert_eq!(unary_reply(&reply_rx).await.1, Some(OK));
+
+    client.send_request(&call(unanswered_point(), "timeout_ms", 200)).await.unwrap();
+    assert_eq!(unary_reply(&reply_rx).await.1, Some(DEADLINE_EXCEEDED));
+
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap();
+    let deadline = (now + Duration::from_millis(200)).as_millis() as u64;
+    client.send_request(&call(unanswered_point(), "deadline", deadline)).await.unwrap();
+    assert_eq!(unary_reply(&reply_rx).await.1, Some(DEADLINE_EXCEEDED));
+}
+
+#[tokio::test]
+async fn calls_past_the_concurrency_limit_are_busy() {
+    let (uri, _) = serve().await;
+    let (reply_tx, reply_rx) = unbounded();
+    let mut client = connect(uri, reply_tx);
+
+    let list_points = |point_count: i64| {
+        let mut meta = Object::new();
+        meta.insert("request_path".into(), "shapes.Shapes/ListPoints".into());
+        meta.insert("timeout_ms".into(), 500.into());
+        let mut summary = Object::new();
+        summary.insert("point_count".into(), point_count.into());
+        Event {
+            data: (summary, meta).into(),
+            ..Event::default()
+        }
+    };
+    assert_eq!(client.send_request(&list_points(UNANSWERED)).await.unwrap(), RequestStatus::Sent);
+    let event = list_points(1);
+    assert_eq!(client.send_request(&event).await.unwrap(), RequestStatus::Busy);
+    no_reply(&reply_rx).await;
+
+    assert_eq!(unary_reply(&reply_rx).await.1, Some(DEADLINE_EXCEEDED));
+    assert_eq!(client.send_request(&event).await.unwrap(), RequestStatus::Sent);
+    assert_eq!(unary_reply(&reply_rx).await.1, Some(OK));
+    let end = next_reply(&reply_rx).await;
+    assert_eq!(end.suffix().meta().get_bool("end_of_stream"), Some(true));
+}
+
+#[tokio::test]
+async fn values_past_the_stream_capacity_are_stream_full() {
+    let (uri, forwarded_rx) = serve().await;
+    let (reply_tx, reply_rx) = unbounded();
+    let mut client = connect(uri, reply_tx);
+
+    // `count` points for stream 1, opening it first if `open` is set.
+    let points = |count: usize, open: bool| {
+        let values = (0..count).map(|i| {
+            let mut meta = Object::new();
+            meta.insert("request_path".into(), "shapes.Shapes/RecordPoints".into());
+            meta.insert("stream_id".into(), 1.into());
+            let command = if open && i == 0 { "open" } else { "data" };
+            meta.insert("stream_command".into(), command.into());
+            (point(), meta)
+        });
+        batch(values.collect())
+    };
+    assert_eq!(client.send_request(&points(3, true)).await.unwrap(), RequestStatus::StreamFull);
+    no_reply(&forwarded_rx).await;
+
+    assert_eq!(client.send_request(&points(2, true)).await.unwrap(), RequestStatus::Sent);
+    for _ in 0..2 {
+        next_reply(&forwarded_rx).await;
+    }
+    let event = points(3, false);
+    assert_eq!(client.send_request(&event).await.unwrap(), RequestStatus::StreamFull);
+    no_reply(&forwarded_rx).await;
+
+    client.send_request(&stream_event(1, 1, "half_close", Value::default())).await.unwrap();
+    assert_eq!(reply(&reply_rx).await, (1, Some(OK)));
+    assert_eq!(forwarded_rx.len(), 1);
+}
@@ -0,0 +1,126 @@
+//! Calls a generated server that requires mutual TLS, using the self-signed certificates in
+//! `tests/fixtures/tls`. The server certificate is only valid for `server.test`.
+
+use std::{fs, path::PathBuf, time::Duration};
+
+use async_std::channel::unbounded;
+use generated::{
+    grpc_client::{EndpointConfig, Endpoints, GrpcClientHandler, TlsConfig},
+    grpc_server::{helloworld::greeter_server::GreeterServer, GrpcServerHandler},
+};
+use tonic::transport::{Certificate, Identity, ServerTlsConfig};
+use tremor_pipeline::Event;
+use tremor_script::EventPayload;
+use tremor_value::value::Object;
+use value_trait::ValueAccess;
+
+const OK: i64 = 0;
+
+fn fixture(name: &str) -> Vec<u8> {
+    let dir = PathBuf::from(std::env::var("GEN_TONIC_IMPLS_FIXTURES").unwrap());
+    fs::read(dir.join("tls").join(name)).unwrap()
+}
+
+/// Serves the generated handler over TLS on a free local port, only accepting clients with a
+/// certificate signed by the test CA, and greets every caller.
+async fn serve() -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (request_tx, request_rx) = unbounded::<EventPayload>();
+    let handler = GrpcServerHandler::new(request_tx);
+    let tls = ServerTlsConfig::new()
+        .identity(Identity::from_pem(fixture("server.pem"), fixture("server.key")))
+        .client_ca_root(Certificate::from_pem(fixture("ca.pem")));
+    tokio::spawn(
+        tonic::transport::Server::builder()
+            .tls_config(tls)
+            .unwrap()
+            .add_service(GreeterServer::new(handler.clone()))
+            .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener)),
+    );
+    tokio::spawn(async move {
+        while let Ok(request) = request_rx.recv().await {
+            let meta = request.suffix().meta();
+            let mut reply_meta = Object::new();
+            reply_meta.insert("request_id".into(), meta.get_u64("request_id").unwrap().into());
+            reply_meta.insert("flag".into(), true.into());
+            let mut reply = Object::new();
+            reply.insert("message".into(), "hello".into());
+            let data: EventPayload = (reply, reply_meta).into();
+            handler.send_reply(Event { data, ..Event::default() }).await.unwrap();
+        }
+    });
+    format!("https://{}", addr)
+}
+
+fn trusted() -> TlsConfig {
+    TlsConfig {
+        ca_certificate: Some(fixture("ca.pem")),
+        client_certificate: Some(fixture("client.pem")),
+        client_key: Some(fixture("client.key")),
+        domain_name: Some(String::from("server.test")),
+    }
+}
+
+/// Sends one greeting and returns the `grpc_status` and message of the reply.
+async fn say_hello(tls: TlsConfig) -> (Option<i64>, Option<String>) {
+    let uri = serve().await;
+    let (reply_tx, reply_rx) = unbounded();
+    let endpoints = Endpoints {
+        default: Some(EndpointConfig {
+            tls: Some(tls),
+            ..EndpointConfig::new(uri)
+        }),
+        ..Endpoints::default()
+    };
+    let mut client = GrpcClientHandler::connect(&endpoints, reply_tx).unwrap();
+
+    let mut meta = Object::new();
+    meta.insert("request_path".into(), "helloworld.Greeter/SayHello".into());
+    let mut request = Object::new();
+    request.insert("name".into(), "tremor".into());
+    let event = Event {
+        data: (request, meta).into(),
+        ..Event::default()
+    };
+    client.send_request(&event).await.unwrap();
+    let reply = tokio::time::timeout(Duration::from_secs(10), reply_rx.recv())
+        .await
+        .expect("no reply")
+        .unwrap();
+    let status = reply.suffix().meta().get_i64("grpc_status");
+    (status, reply.suffix().value().get_str("message").map(String::from))
+}
+
+#[tokio::test]
+async fn mutual_tls() {
+    assert_eq!(say_hello(trusted()).await, (Some(OK), Some(String::from("hello"))));
+}
+
+#[tokio::test]
+async fn server_certificate_needs_the_ca_override() {
+    let tls = TlsConfig {
+        ca_certificate: None,
+        ..trusted()
+    };
+    assert_ne!(say_hello(tls).await.0, Some(OK));
+}
+
+#[tokio::test]
+async fn server_certificate_is_checked_against_the_domain_name() {
+    let tls = TlsConfig {
+        domain_name: Some(String::from("other.test")),
+        ..trusted()
+    };
+    assert_ne!(say_hello(tls).await.0, Some(OK));
+}
+
+#[tokio::test]
+async fn server_requires_a_client_identity() {
+    let tls = TlsConfig {
+        client_certificate: None,
+        client_key: None,
+        ..trusted()
+    };
+    assert_ne!(say_hello(tls).await.0, Some(OK));
+}
@@ -0,0 +1,69 @@
+//! Stand-in for the parts of `tremor-pipeline` the generated bindings use.
+
+use tremor_script::EventPayload;
+use tremor_value::Value;
+use value_trait::ValueAccess;
+
+#[derive(Debug, Clone, Default)]
+pub struct EventId {
+    source_id: u64,
+    stream_id: u64,
+    event_id: u64,
+}
+
+impl EventId {
+    pub fn new(source_id: u64, stream_id: u64, event_id: u64) -> Self {
+        EventId {
+            source_id,
+            stream_id,
+            event_id,
+        }
+    }
+
+    pub fn source_id(&self) -> u64 {
+        self.source_id
+    }
+
+    pub fn stream_id(&self) -> u64 {
+        self.stream_id
+    }
+
+    pub fn event_id(&self) -> u64 {
+        self.event_id
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Event {
+    pub id: EventId,
+    pub data: EventPayload,
+    pub is_batch: bool,
+}
+
+impl Event {
+    pub fn value_meta_iter(&self) -> ValueMetaIter<'_> {
+        ValueMetaIter { event: self, index: 0 }
+    }
+}
+
+/// Yields the value and meta of an event, or of every `{"data": {"value": .., "meta": ..}}`
+/// element of a batch.
+pub struct ValueMetaIter<'value> {
+    event: &'value Event,
+    index: usize,
+}
+
+impl<'value> Iterator for ValueMetaIter<'value> {
+    type Item = (&'value Value<'value>, &'value Value<'value>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let data = self.event.data.suffix();
+        let index = self.index;
+        self.index += 1;
+        if !self.event.is_batch {
+            return if index == 0 { Some((data.value(), data.meta())) } else { None };
+        }
+        let element = data.value().as_array()?.get(index)?.get("data")?;
+        Some((element.get("value")?, element.get("meta")?))
+    }
+}
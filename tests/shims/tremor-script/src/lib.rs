@@ -0,0 +1,45 @@
+//! Stand-in for the parts of `tremor-script` the generated bindings use.
+
+use tremor_value::Value;
+
+#[derive(Debug, Clone, Default)]
+pub struct ValueAndMeta<'event> {
+    value: Value<'event>,
+    meta: Value<'event>,
+}
+
+impl<'event> ValueAndMeta<'event> {
+    pub fn value(&self) -> &Value<'event> {
+        &self.value
+    }
+
+    pub fn meta(&self) -> &Value<'event> {
+        &self.meta
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct EventPayload {
+    data: ValueAndMeta<'static>,
+}
+
+impl EventPayload {
+    pub fn suffix(&self) -> &ValueAndMeta<'static> {
+        &self.data
+    }
+}
+
+impl<T1, T2> From<(T1, T2)> for EventPayload
+where
+    T1: Into<Value<'static>>,
+    T2: Into<Value<'static>>,
+{
+    fn from((value, meta): (T1, T2)) -> Self {
+        EventPayload {
+            data: ValueAndMeta {
+                value: value.into(),
+                meta: meta.into(),
+            },
+        }
+    }
+}
@@ -0,0 +1,249 @@
+//! Stand-in for the parts of `tremor-value` the generated bindings use, so `compile_check` can
+//! build and run them without a tremor checkout. Conversions go through `serde_json`.
+
+use std::{convert::TryFrom, fmt, ops::Index};
+
+use value_trait::{ValueAccess, ValueType};
+
+pub use value_trait::StaticNode;
+
+pub mod value {
+    pub type Object<'value> = halfbrown::HashMap<beef::Cow<'value, str>, super::Value<'value>>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value<'value> {
+    Static(StaticNode),
+    String(beef::Cow<'value, str>),
+    Array(Vec<Value<'value>>),
+    Object(Box<value::Object<'value>>),
+}
+
+impl<'value> Value<'value> {
+    pub fn clone_static(&self) -> Value<'static> {
+        match self {
+            Value::Static(node) => Value::Static(*node),
+            Value::String(s) => Value::String(beef::Cow::owned(s.to_string())),
+            Value::Array(values) => Value::Array(values.iter().map(Value::clone_static).collect()),
+            Value::Object(object) => Value::Object(Box::new(
+                object
+                    .iter()
+                    .map(|(key, value)| (beef::Cow::owned(key.to_string()), value.clone_static()))
+                    .collect(),
+            )),
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        match self {
+            Value::Static(StaticNode::Null) => serde_json::Value::Null,
+            Value::Static(StaticNode::Bool(b)) => serde_json::Value::Bool(*b),
+            Value::Static(StaticNode::I64(i)) => serde_json::Value::from(*i),
+            Value::Static(StaticNode::U64(u)) => serde_json::Value::from(*u),
+            Value::Static(StaticNode::F64(f)) => serde_json::Value::from(*f),
+            #[allow(unreachable_patterns)]
+            Value::Static(_) => serde_json::Value::Null,
+            Value::String(s) => serde_json::Value::from(s.to_string()),
+            Value::Array(values) => values.iter().map(Value::to_json).collect(),
+            Value::Object(object) => serde_json::Value::Object(
+                object.iter().map(|(key, value)| (key.to_string(), value.to_json())).collect(),
+            ),
+        }
+    }
+
+    fn from_json(json: serde_json::Value) -> Value<'static> {
+        match json {
+            serde_json::Value::Null => Value::Static(StaticNode::Null),
+            serde_json::Value::Bool(b) => Value::Static(StaticNode::Bool(b)),
+            serde_json::Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+                (Some(u), _) => Value::Static(StaticNode::U64(u)),
+                (None, Some(i)) => Value::Static(StaticNode::I64(i)),
+                (None, None) => Value::Static(StaticNode::F64(n.as_f64().unwrap_or_default())),
+            },
+            serde_json::Value::String(s) => Value::from(s),
+            serde_json::Value::Array(values) => Value::Array(values.into_iter().map(Value::from_json).collect()),
+            serde_json::Value::Object(object) => Value::Object(Box::new(
+                object
+                    .into_iter()
+                    .map(|(key, value)| (beef::Cow::owned(key), Value::from_json(value)))
+                    .collect(),
+            )),
+        }
+    }
+}
+
+impl<'value> Default for Value<'value> {
+    fn default() -> Self {
+        Value::Static(StaticNode::Null)
+    }
+}
+
+impl<'value> From<&'value str> for Value<'value> {
+    fn from(s: &'value str) -> Self {
+        Value::String(s.into())
+    }
+}
+
+impl<'value> From<String> for Value<'value> {
+    fn from(s: String) -> Self {
+        Value::String(s.into())
+    }
+}
+
+impl<'value> From<bool> for Value<'value> {
+    fn from(b: bool) -> Self {
+        Value::Static(StaticNode::Bool(b))
+    }
+}
+
+impl<'value> From<i32> for Value<'value> {
+    fn from(i: i32) -> Self {
+        Value::Static(StaticNode::I64(i64::from(i)))
+    }
+}
+
+impl<'value> From<i64> for Value<'value> {
+    fn from(i: i64) -> Self {
+        Value::Static(StaticNode::I64(i))
+    }
+}
+
+impl<'value> From<u64> for Value<'value> {
+    fn from(u: u64) -> Self {
+        Value::Static(StaticNode::U64(u))
+    }
+}
+
+impl<'value> From<Vec<Value<'value>>> for Value<'value> {
+    fn from(values: Vec<Value<'value>>) -> Self {
+        Value::Array(values)
+    }
+}
+
+impl<'value> From<value::Object<'value>> for Value<'value> {
+    fn from(object: value::Object<'value>) -> Self {
+        Value::Object(Box::new(object))
+    }
+}
+
+impl<'value> ValueAccess for Value<'value> {
+    type Target = Value<'value>;
+    type Key = beef::Cow<'value, str>;
+    type Array = Vec<Value<'value>>;
+    type Object = value::Object<'value>;
+
+    fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Static(StaticNode::Bool(b)) => Some(*b),
+            _ => None,
+        }
+    }
+
+    fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Static(StaticNode::I64(i)) => Some(*i),
+            Value::Static(StaticNode::U64(u)) => i64::try_from(*u).ok(),
+            _ => None,
+        }
+    }
+
+    fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Static(StaticNode::I64(i)) => u64::try_from(*i).ok(),
+            Value::Static(StaticNode::U64(u)) => Some(*u),
+            _ => None,
+        }
+    }
+
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Static(StaticNode::F64(f)) => Some(*f),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn as_array(&self) -> Option<&Vec<Value<'value>>> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    fn as_object(&self) -> Option<&value::Object<'value>> {
+        match self {
+            Value::Object(object) => Some(object),
+            _ => None,
+        }
+    }
+}
+
+impl<'value> value_trait::Value for Value<'value> {
+    fn value_type(&self) -> ValueType {
+        match self {
+            Value::Static(StaticNode::Bool(_)) => ValueType::Bool,
+            Value::Static(StaticNode::I64(_)) => ValueType::I64,
+            Value::Static(StaticNode::U64(_)) => ValueType::U64,
+            Value::Static(StaticNode::F64(_)) => ValueType::F64,
+            Value::Static(_) => ValueType::Null,
+            Value::String(_) => ValueType::String,
+            Value::Array(_) => ValueType::Array,
+            Value::Object(_) => ValueType::Object,
+        }
+    }
+
+    fn is_null(&self) -> bool {
+        matches!(self, Value::Static(StaticNode::Null))
+    }
+}
+
+impl<'value> Index<usize> for Value<'value> {
+    type Output = Value<'value>;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        match self {
+            Value::Array(values) => &values[index],
+            _ => panic!("not an array"),
+        }
+    }
+}
+
+// `value_trait::Value` requires comparisons with every primitive.
+macro_rules! impl_partial_eq {
+    ($($ty:ty),*) => {
+        $(
+            impl<'value> PartialEq<$ty> for Value<'value> {
+                fn eq(&self, other: &$ty) -> bool {
+                    self.to_json() == serde_json::json!(other)
+                }
+            }
+        )*
+    };
+}
+
+impl_partial_eq!(i8, i16, i32, i64, i128, u8, u16, u32, u64, u128, f32, f64, String, bool, ());
+
+#[derive(Debug)]
+pub struct Error(serde_json::Error);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub fn to_value<T: serde::Serialize>(value: T) -> Result<Value<'static>, Error> {
+    serde_json::to_value(value).map(Value::from_json).map_err(Error)
+}
+
+pub fn structurize<T: serde::de::DeserializeOwned>(value: Value) -> Result<T, Error> {
+    serde_json::from_value(value.to_json()).map_err(Error)
+}
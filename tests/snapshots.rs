@@ -0,0 +1,106 @@
+//! Compares the generated bindings for the protos in `tests/fixtures` against the committed
+//! snapshots in `tests/snapshots`.
+//!
+//! Run with `GEN_TONIC_IMPLS_BLESS=1` to overwrite the snapshots with the current output.
+
+use std::{env, fs, path::PathBuf, time::Duration};
+
+use gen_tonic_impls::Config;
+
+const GENERATED_FILES: &[&str] = &["grpc_client.rs", "grpc_server.rs"];
+
+fn assert_snapshot(name: &str, protos: &[&str]) {
+    assert_snapshot_with(name, protos, &mut Config::new());
+}
+
+fn assert_snapshot_with(name: &str, protos: &[&str], config: &mut Config) {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let fixtures = root.join("fixtures");
+    let snapshots = root.join("snapshots").join(name);
+    let protos = protos.iter().map(|proto| fixtures.join(proto)).collect::<Vec<_>>();
+    let out_dir = tempfile::tempdir().unwrap();
+    config.out_dir(out_dir.path()).compile(&protos, &[&fixtures]).unwrap();
+
+    let bless = env::var_os("GEN_TONIC_IMPLS_BLESS").is_some();
+    for file_name in GENERATED_FILES {
+        let actual = fs::read_to_string(out_dir.path().join(file_name)).unwrap();
+        let snapshot = snapshots.join(file_name);
+        if bless {
+            fs::create_dir_all(&snapshots).unwrap();
+            fs::write(&snapshot, &actual).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&snapshot)
+            .unwrap_or_else(|e| panic!("cannot read {}: {}, rerun with GEN_TONIC_IMPLS_BLESS=1", snapshot.display(), e));
+        assert!(
+            actual == expected,
+            "{} differs from {}, rerun with GEN_TONIC_IMPLS_BLESS=1 to update it",
+            file_name,
+            snapshot.display()
+        );
+    }
+}
+
+#[test]
+fn unary() {
+    assert_snapshot("unary", &["unary.proto"]);
+}
+
+#[test]
+fn streaming_shapes() {
+    assert_snapshot("streaming_shapes", &["shapes.proto"]);
+}
+
+#[test]
+fn nested_packages() {
+    assert_snapshot("nested_packages", &["acme/billing/v1/invoices.proto"]);
+}
+
+#[test]
+fn imported_types() {
+    assert_snapshot("imported_types", &["acme/payments/v1/payments.proto"]);
+}
+
+#[test]
+fn field_imports() {
+    assert_snapshot("field_imports", &["acme/orders/v1/orders.proto"]);
+}
+
+#[test]
+fn keyword_fields() {
+    assert_snapshot("keyword_fields", &["keywords.proto"]);
+}
+
+#[test]
+fn multiple_services() {
+    assert_snapshot("multiple_services", &["multiple_services.proto"]);
+}
+
+#[test]
+fn method_filter() {
+    assert_snapshot_with(
+        "method_filter",
+        &["multiple_services.proto"],
+        Config::new().method("inventory.Items/Get").method("inventory.Items/List"),
+    );
+}
+
+#[test]
+fn method_options() {
+    assert_snapshot_with(
+        "method_options",
+        &["shapes.proto"],
+        Config::new()
+            .timeout("shapes.Shapes/GetPoint", Duration::from_millis(500))
+            .timeout("shapes.Shapes/RecordPoints", Duration::from_micros(250))
+            .timeout("shapes.Shapes/ListPoints", Duration::from_secs(30))
+            .concurrency_limit("shapes.Shapes/GetPoint", 16)
+            .stream_capacity("shapes.Shapes/Chat", 8)
+            .stream_idle_timeout(Duration::from_secs(60)),
+    );
+}
+
+#[test]
+fn tls() {
+    assert_snapshot_with("tls", &["unary.proto"], Config::new().tls(true));
+}
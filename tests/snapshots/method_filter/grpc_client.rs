@@ -0,0 +1,891 @@
+pub mod inventory {
+    tonic::include_proto!("inventory");
+}
+use value_trait::ValueAccess;
+#[derive(Debug)]
+pub enum Error {
+    Status(tonic::Status),
+    Transport(tonic::transport::Error),
+    Value(tremor_value::Error),
+    ChannelClosed,
+}
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Status(status) => write!(f, "gRPC call failed: {}", status),
+            Error::Transport(e) => write!(f, "gRPC transport error: {}", e),
+            Error::Value(e) => write!(f, "value conversion failed: {}", e),
+            Error::ChannelClosed => write!(f, "channel closed"),
+        }
+    }
+}
+impl std::error::Error for Error {}
+impl From<tonic::Status> for Error {
+    fn from(status: tonic::Status) -> Self {
+        Error::Status(status)
+    }
+}
+impl From<tonic::transport::Error> for Error {
+    fn from(e: tonic::transport::Error) -> Self {
+        Error::Transport(e)
+    }
+}
+impl From<tremor_value::Error> for Error {
+    fn from(e: tremor_value::Error) -> Self {
+        Error::Value(e)
+    }
+}
+impl<T> From<async_std::channel::SendError<T>> for Error {
+    fn from(_: async_std::channel::SendError<T>) -> Self {
+        Error::ChannelClosed
+    }
+}
+pub type Result<T> = std::result::Result<T, Error>;
+fn into_error<E>(e: E) -> Error
+where
+    Error: From<E>,
+{
+    Error::from(e)
+}
+struct StaticValue(tremor_value::Value<'static>);
+#[derive(Debug, Clone)]
+struct ReplyContext {
+    request_path: &'static str,
+    event_id: tremor_value::Value<'static>,
+    stream_id: Option<u64>,
+    correlation: Option<tremor_value::Value<'static>>,
+    sequence: u64,
+}
+impl ReplyContext {
+    fn new(
+        request_path: &'static str,
+        event_id: &tremor_pipeline::EventId,
+        meta: &tremor_value::Value,
+    ) -> Self {
+        let mut id = tremor_value::value::Object::with_capacity(3);
+        id.insert("source_id".into(), event_id.source_id().into());
+        id.insert("stream_id".into(), event_id.stream_id().into());
+        id.insert("event_id".into(), event_id.event_id().into());
+        ReplyContext {
+            request_path,
+            event_id: tremor_value::Value::from(id),
+            stream_id: meta.get_u64("stream_id"),
+            correlation: meta
+                .get("correlation")
+                .map(|correlation| correlation.clone_static()),
+            sequence: 0,
+        }
+    }
+    fn meta(&self, code: tonic::Code, message: &str) -> tremor_value::value::Object<'static> {
+        let mut meta = tremor_value::value::Object::with_capacity(9);
+        meta.insert("request_path".into(), self.request_path.into());
+        meta.insert("event_id".into(), self.event_id.clone());
+        meta.insert("sequence".into(), self.sequence.into());
+        if let Some(stream_id) = self.stream_id {
+            meta.insert("stream_id".into(), stream_id.into());
+        }
+        if let Some(correlation) = &self.correlation {
+            meta.insert("correlation".into(), correlation.clone());
+        }
+        meta.insert("grpc_status".into(), (code as i32).into());
+        meta.insert("grpc_message".into(), message.to_string().into());
+        meta
+    }
+    fn reply(
+        &mut self,
+        message: tremor_value::Value<'static>,
+        headers: tremor_value::Value<'static>,
+    ) -> tremor_script::EventPayload {
+        let mut meta = self.meta(tonic::Code::Ok, "");
+        meta.insert("headers".into(), headers);
+        self.sequence += 1;
+        (message, meta).into()
+    }
+    fn end(&self, trailers: Option<&tonic::metadata::MetadataMap>) -> tremor_script::EventPayload {
+        let mut meta = self.meta(tonic::Code::Ok, "");
+        meta.insert("end_of_stream".into(), true.into());
+        if let Some(trailers) = trailers {
+            meta.insert("trailers".into(), metadata_value(trailers));
+        }
+        (
+            tremor_value::Value::Static(value_trait::StaticNode::Null),
+            meta,
+        )
+            .into()
+    }
+    fn error(&self, status: &tonic::Status) -> tremor_script::EventPayload {
+        let mut meta = self.meta(status.code(), status.message());
+        meta.insert("trailers".into(), metadata_value(status.metadata()));
+        (
+            tremor_value::Value::Static(value_trait::StaticNode::Null),
+            meta,
+        )
+            .into()
+    }
+}
+fn metadata_value(metadata: &tonic::metadata::MetadataMap) -> tremor_value::Value<'static> {
+    let mut object = tremor_value::value::Object::with_capacity(metadata.len());
+    for entry in metadata.iter() {
+        let (key, value) = match entry {
+            tonic::metadata::KeyAndValueRef::Ascii(key, value) => match value.to_str() {
+                Ok(value) => (key.as_str(), value.to_string()),
+                Err(_) => continue,
+            },
+            tonic::metadata::KeyAndValueRef::Binary(key, value) => match value.to_bytes() {
+                Ok(value) => (key.as_str(), base64::encode(value)),
+                Err(_) => continue,
+            },
+        };
+        match object.get_mut(key) {
+            Some(tremor_value::Value::Array(values)) => values.push(value.into()),
+            Some(existing) => {
+                let first = std::mem::replace(
+                    existing,
+                    tremor_value::Value::Static(value_trait::StaticNode::Null),
+                );
+                *existing = tremor_value::Value::from(vec![first, value.into()]);
+            }
+            None => {
+                object.insert(key.to_string().into(), value.into());
+            }
+        }
+    }
+    tremor_value::Value::from(object)
+}
+fn apply_timeout<T>(
+    request: &mut tonic::Request<T>,
+    meta: &tremor_value::Value,
+    default: Option<std::time::Duration>,
+) -> Option<std::time::Instant> {
+    let timeout = if let Some(timeout_ms) = meta.get_u64("timeout_ms") {
+        Some(std::time::Duration::from_millis(timeout_ms))
+    } else if let Some(deadline) = meta.get_u64("deadline") {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        Some(
+            std::time::Duration::from_millis(deadline)
+                .checked_sub(now)
+                .unwrap_or_default(),
+        )
+    } else {
+        default
+    }?;
+    request.set_timeout(timeout);
+    Some(std::time::Instant::now() + timeout)
+}
+async fn with_deadline<T, F>(
+    deadline: Option<std::time::Instant>,
+    future: F,
+) -> std::result::Result<T, tonic::Status>
+where
+    F: std::future::Future<Output = std::result::Result<T, tonic::Status>>,
+{
+    match deadline {
+        Some(deadline) => {
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            match async_std::future::timeout(remaining, future).await {
+                Ok(Err(_)) if std::time::Instant::now() >= deadline => {
+                    Err(tonic::Status::deadline_exceeded("deadline exceeded"))
+                }
+                Ok(result) => result,
+                Err(_) => Err(tonic::Status::deadline_exceeded("deadline exceeded")),
+            }
+        }
+        None => future.await,
+    }
+}
+fn apply_headers(
+    metadata: &mut tonic::metadata::MetadataMap,
+    meta: &tremor_value::Value,
+) -> std::result::Result<(), tonic::Status> {
+    let headers = match meta.get_object("headers") {
+        Some(headers) => headers,
+        None => return Ok(()),
+    };
+    for (key, values) in headers.iter() {
+        let values = match values.as_array() {
+            Some(values) => values.iter().collect::<Vec<_>>(),
+            None => vec![values],
+        };
+        for value in values {
+            let value = value.as_str().ok_or_else(|| {
+                tonic::Status::invalid_argument(format!("metadata `{}` must be a string", key))
+            })?;
+            if key.ends_with("-bin") {
+                let value = base64::decode(value).map_err(|e| {
+                    tonic::Status::invalid_argument(format!(
+                        "metadata `{}` is not valid base64: {}",
+                        key, e
+                    ))
+                })?;
+                let key = tonic::metadata::BinaryMetadataKey::from_bytes(key.as_bytes()).map_err(
+                    |_| tonic::Status::invalid_argument(format!("invalid metadata key `{}`", key)),
+                )?;
+                metadata.append_bin(key, tonic::metadata::MetadataValue::from_bytes(&value));
+            } else {
+                let value = value.parse().map_err(|_| {
+                    tonic::Status::invalid_argument(format!("invalid value for metadata `{}`", key))
+                })?;
+                let key = tonic::metadata::AsciiMetadataKey::from_bytes(key.as_bytes()).map_err(
+                    |_| tonic::Status::invalid_argument(format!("invalid metadata key `{}`", key)),
+                )?;
+                metadata.append(key, value);
+            }
+        }
+    }
+    Ok(())
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StreamCommand {
+    Open,
+    Data,
+    HalfClose,
+    Cancel,
+}
+impl StreamCommand {
+    fn from_meta(meta: &tremor_value::Value) -> std::result::Result<Self, tonic::Status> {
+        match meta.get_str("stream_command") {
+            None | Some("data") => Ok(StreamCommand::Data),
+            Some("open") => Ok(StreamCommand::Open),
+            Some("half_close") => Ok(StreamCommand::HalfClose),
+            Some("cancel") => Ok(StreamCommand::Cancel),
+            Some(command) => Err(tonic::Status::invalid_argument(format!(
+                "unknown stream command `{}`",
+                command
+            ))),
+        }
+    }
+}
+fn streams_have_room(
+    event: &tremor_pipeline::Event,
+    streams: &hashbrown::HashMap<StreamKey, StreamHandle>,
+    method: &'static str,
+    capacity: usize,
+) -> bool {
+    let mut queued: hashbrown::HashMap<u64, usize> = hashbrown::HashMap::new();
+    for (value, meta) in event.value_meta_iter() {
+        let (stream_id, command) = match (meta.get_u64("stream_id"), StreamCommand::from_meta(meta))
+        {
+            (Some(stream_id), Ok(command)) => (stream_id, command),
+            _ => continue,
+        };
+        let is_null = matches!(
+            value,
+            tremor_value::Value::Static(value_trait::StaticNode::Null)
+        );
+        if command == StreamCommand::Data || (command != StreamCommand::Cancel && !is_null) {
+            *queued.entry(stream_id).or_insert(0) += 1;
+        }
+    }
+    queued.into_iter().all(
+        |(stream_id, count)| match streams.get(&(method, stream_id)) {
+            Some(stream) if stream.tx.is_closed() => true,
+            Some(stream) => count <= stream.tx.capacity().unwrap_or(usize::MAX) - stream.tx.len(),
+            None => count <= capacity,
+        },
+    )
+}
+type StreamKey = (&'static str, u64);
+#[derive(Debug)]
+struct StreamHandle {
+    tx: async_std::channel::Sender<StaticValue>,
+    cancel: async_std::channel::Sender<tonic::Status>,
+    last_active: std::time::Instant,
+}
+struct Cancellable<F> {
+    future: std::pin::Pin<Box<F>>,
+    cancel: std::pin::Pin<Box<dyn std::future::Future<Output = tonic::Status> + Send>>,
+}
+impl<T, F> std::future::Future for Cancellable<F>
+where
+    F: std::future::Future<Output = std::result::Result<T, tonic::Status>>,
+{
+    type Output = std::result::Result<T, tonic::Status>;
+    fn poll(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Self::Output> {
+        if let std::task::Poll::Ready(status) = self.cancel.as_mut().poll(cx) {
+            return std::task::Poll::Ready(Err(status));
+        }
+        self.future.as_mut().poll(cx)
+    }
+}
+struct RequestStream<T> {
+    rx: async_std::channel::Receiver<StaticValue>,
+    cancel: async_std::channel::Sender<tonic::Status>,
+    structurize: fn(tremor_value::Value<'static>) -> std::result::Result<T, tremor_value::Error>,
+    failed: bool,
+}
+impl<T> async_std::stream::Stream for RequestStream<T> {
+    type Item = T;
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<T>> {
+        let this = self.get_mut();
+        if this.failed {
+            return std::task::Poll::Pending;
+        }
+        let value = match async_std::stream::Stream::poll_next(std::pin::Pin::new(&mut this.rx), cx)
+        {
+            std::task::Poll::Ready(Some(value)) => value,
+            std::task::Poll::Ready(None) => return std::task::Poll::Ready(None),
+            std::task::Poll::Pending => return std::task::Poll::Pending,
+        };
+        match (this.structurize)(value.0) {
+            Ok(message) => std::task::Poll::Ready(Some(message)),
+            Err(e) => {
+                this.failed = true;
+                let _ = this
+                    .cancel
+                    .try_send(tonic::Status::invalid_argument(e.to_string()));
+                std::task::Poll::Pending
+            }
+        }
+    }
+}
+fn cancellable<F>(
+    cancel: &async_std::channel::Receiver<tonic::Status>,
+    future: F,
+) -> Cancellable<F> {
+    let cancel = cancel.clone();
+    Cancellable {
+        future: Box::pin(future),
+        cancel: Box::pin(async move {
+            cancel
+                .recv()
+                .await
+                .unwrap_or_else(|_| tonic::Status::cancelled("client handler closed"))
+        }),
+    }
+}
+fn cancellable_stream<F>(
+    cancel: &async_std::channel::Receiver<tonic::Status>,
+    shutdown: &async_std::channel::Receiver<tonic::Status>,
+    future: F,
+) -> Cancellable<Cancellable<F>> {
+    cancellable(shutdown, cancellable(cancel, future))
+}
+async fn send_reply(
+    reply_tx: &async_std::channel::Sender<tremor_script::EventPayload>,
+    shutdown: &async_std::channel::Receiver<tonic::Status>,
+    event: tremor_script::EventPayload,
+) -> bool {
+    let event = match reply_tx.try_send(event) {
+        Ok(()) => return true,
+        Err(async_std::channel::TrySendError::Full(event)) => event,
+        Err(async_std::channel::TrySendError::Closed(_)) => return false,
+    };
+    let send = async move {
+        reply_tx
+            .send(event)
+            .await
+            .map_err(|_| tonic::Status::cancelled("reply channel closed"))
+    };
+    cancellable(shutdown, send).await.is_ok()
+}
+#[derive(Debug, Clone)]
+struct TaskToken {
+    shutdown: async_std::channel::Receiver<tonic::Status>,
+    _alive: async_std::channel::Sender<()>,
+}
+#[derive(Debug, Clone)]
+struct InFlight {
+    limit: Option<usize>,
+    count: std::sync::Arc<std::sync::atomic::AtomicUsize>,
+}
+impl InFlight {
+    fn new(limit: Option<usize>) -> Self {
+        InFlight {
+            limit,
+            count: std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0)),
+        }
+    }
+    fn try_acquire(&self, n: usize) -> Option<Vec<InFlightPermit>> {
+        let mut count = self.count.load(std::sync::atomic::Ordering::Acquire);
+        loop {
+            if self.limit.map_or(false, |limit| count + n > limit) {
+                return None;
+            }
+            match self.count.compare_exchange_weak(
+                count,
+                count + n,
+                std::sync::atomic::Ordering::AcqRel,
+                std::sync::atomic::Ordering::Acquire,
+            ) {
+                Ok(_) => return Some((0..n).map(|_| InFlightPermit(self.count.clone())).collect()),
+                Err(actual) => count = actual,
+            }
+        }
+    }
+}
+#[derive(Debug)]
+struct InFlightPermit(std::sync::Arc<std::sync::atomic::AtomicUsize>);
+impl Drop for InFlightPermit {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, std::sync::atomic::Ordering::AcqRel);
+    }
+}
+#[derive(Debug, Clone)]
+pub struct EndpointConfig {
+    pub uri: String,
+    pub connect_timeout: Option<std::time::Duration>,
+    pub http2_keep_alive_interval: Option<std::time::Duration>,
+    pub keep_alive_timeout: Option<std::time::Duration>,
+    pub initial_stream_window_size: Option<u32>,
+    pub initial_connection_window_size: Option<u32>,
+    pub concurrency_limit: Option<usize>,
+    pub rate_limit: Option<(u64, std::time::Duration)>,
+    pub tcp_nodelay: Option<bool>,
+}
+impl EndpointConfig {
+    pub fn new(uri: impl Into<String>) -> Self {
+        EndpointConfig {
+            uri: uri.into(),
+            connect_timeout: None,
+            http2_keep_alive_interval: None,
+            keep_alive_timeout: None,
+            initial_stream_window_size: None,
+            initial_connection_window_size: None,
+            concurrency_limit: None,
+            rate_limit: None,
+            tcp_nodelay: None,
+        }
+    }
+    fn endpoint(&self) -> Result<tonic::transport::Endpoint> {
+        let mut endpoint = tonic::transport::Endpoint::new(self.uri.clone()).map_err(into_error)?;
+        if let Some(timeout) = self.connect_timeout {
+            endpoint = endpoint.connect_timeout(timeout);
+        }
+        if let Some(interval) = self.http2_keep_alive_interval {
+            endpoint = endpoint.http2_keep_alive_interval(interval);
+        }
+        if let Some(timeout) = self.keep_alive_timeout {
+            endpoint = endpoint.keep_alive_timeout(timeout);
+        }
+        if let Some(size) = self.initial_stream_window_size {
+            endpoint = endpoint.initial_stream_window_size(size);
+        }
+        if let Some(size) = self.initial_connection_window_size {
+            endpoint = endpoint.initial_connection_window_size(size);
+        }
+        if let Some(limit) = self.concurrency_limit {
+            endpoint = endpoint.concurrency_limit(limit);
+        }
+        if let Some((limit, period)) = self.rate_limit {
+            endpoint = endpoint.rate_limit(limit, period);
+        }
+        if let Some(nodelay) = self.tcp_nodelay {
+            endpoint = endpoint.tcp_nodelay(nodelay);
+        }
+        Ok(endpoint)
+    }
+}
+#[derive(Debug, Clone, Default)]
+pub struct Endpoints {
+    pub default: Option<EndpointConfig>,
+    pub services: hashbrown::HashMap<String, EndpointConfig>,
+}
+impl Endpoints {
+    fn endpoint(&self, service: &str) -> Result<tonic::transport::Endpoint> {
+        let config = self
+            .services
+            .get(service)
+            .or_else(|| self.default.as_ref())
+            .ok_or_else(|| {
+                into_error(tonic::Status::invalid_argument(format!(
+                    "no endpoint configured for `{}`",
+                    service
+                )))
+            })?;
+        config.endpoint()
+    }
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RequestStatus {
+    Sent,
+    Busy,
+    StreamFull,
+}
+#[derive(Debug)]
+pub struct GrpcClientHandler {
+    clients: hashbrown::HashMap<String, Box<dyn TremorGrpcClient>>,
+    streams: hashbrown::HashMap<StreamKey, StreamHandle>,
+    methods: hashbrown::HashMap<String, prost_types::MethodDescriptorProto>,
+    in_flight: hashbrown::HashMap<String, InFlight>,
+    reply_tx: async_std::channel::Sender<tremor_script::EventPayload>,
+    shutdown: async_std::channel::Sender<tonic::Status>,
+    tasks: Option<TaskToken>,
+    tasks_done: async_std::channel::Receiver<()>,
+}
+impl GrpcClientHandler {
+    pub fn connect(
+        endpoints: &Endpoints,
+        reply_tx: async_std::channel::Sender<tremor_script::EventPayload>,
+    ) -> Result<Self> {
+        let mut client_map: hashbrown::HashMap<String, Box<dyn TremorGrpcClient>> =
+            hashbrown::HashMap::new();
+        let mut methods_map: hashbrown::HashMap<String, prost_types::MethodDescriptorProto> =
+            hashbrown::HashMap::new();
+        let streams_map: hashbrown::HashMap<StreamKey, StreamHandle> = hashbrown::HashMap::new();
+        let mut in_flight_map: hashbrown::HashMap<String, InFlight> = hashbrown::HashMap::new();
+        let (shutdown, shutdown_rx) = async_std::channel::bounded::<tonic::Status>(1);
+        let (alive, tasks_done) = async_std::channel::bounded::<()>(1);
+        let channel = endpoints
+            .endpoint("inventory.Items")?
+            .connect_lazy()
+            .map_err(into_error)?;
+        let inventory_items_client = inventory::items_client::ItemsClient::new(channel);
+        client_map.insert(
+            String::from("inventory.Items"),
+            Box::new(inventory_items_client),
+        );
+        methods_map.insert(
+            String::from("inventory.Items/Get"),
+            prost_types::MethodDescriptorProto {
+                name: Some(String::from("Get")),
+                input_type: Some(String::from(".inventory.ItemRequest")),
+                output_type: Some(String::from(".inventory.Item")),
+                client_streaming: Some(false),
+                server_streaming: Some(false),
+                options: None,
+            },
+        );
+        methods_map.insert(
+            String::from("inventory.Items/List"),
+            prost_types::MethodDescriptorProto {
+                name: Some(String::from("List")),
+                input_type: Some(String::from(".inventory.ItemRequest")),
+                output_type: Some(String::from(".inventory.Item")),
+                client_streaming: Some(false),
+                server_streaming: Some(true),
+                options: None,
+            },
+        );
+        in_flight_map.insert(String::from("inventory.Items/Get"), InFlight::new(None));
+        in_flight_map.insert(String::from("inventory.Items/List"), InFlight::new(None));
+        Ok(GrpcClientHandler {
+            clients: client_map,
+            methods: methods_map,
+            streams: streams_map,
+            in_flight: in_flight_map,
+            reply_tx,
+            shutdown,
+            tasks: Some(TaskToken {
+                shutdown: shutdown_rx,
+                _alive: alive,
+            }),
+            tasks_done,
+        })
+    }
+    pub async fn send_request(&mut self, event: &tremor_pipeline::Event) -> Result<RequestStatus> {
+        self.reap_streams();
+        let tasks = match &self.tasks {
+            Some(tasks) => tasks,
+            None => return Ok(RequestStatus::Sent),
+        };
+        let request_path = event
+            .value_meta_iter()
+            .next()
+            .and_then(|(_, meta)| meta.get_str("request_path"))
+            .map(String::from);
+        if let Some(path) = request_path {
+            let client_key = path.split("/").collect::<Vec<&str>>()[0];
+            if let Some(((method, client), in_flight)) = self
+                .methods
+                .get(&path)
+                .zip(self.clients.get_mut(client_key))
+                .zip(self.in_flight.get(&path))
+            {
+                let status = if !method.client_streaming() && !method.server_streaming() {
+                    client
+                        .send_unary_request(&path, event, self.reply_tx.clone(), tasks, in_flight)
+                        .await?
+                } else if method.client_streaming() && !method.server_streaming() {
+                    client
+                        .send_client_stream_request(
+                            &path,
+                            event,
+                            self.reply_tx.clone(),
+                            tasks,
+                            &mut self.streams,
+                        )
+                        .await?
+                } else if !method.client_streaming() && method.server_streaming() {
+                    client
+                        .send_server_stream_request(
+                            &path,
+                            event,
+                            self.reply_tx.clone(),
+                            tasks,
+                            in_flight,
+                        )
+                        .await?
+                } else {
+                    client
+                        .send_binary_stream_request(
+                            &path,
+                            event,
+                            self.reply_tx.clone(),
+                            tasks,
+                            &mut self.streams,
+                        )
+                        .await?
+                };
+                return Ok(status);
+            }
+        }
+        Ok(RequestStatus::Sent)
+    }
+    pub fn reap_streams(&mut self) {
+        let idle_timeout: Option<std::time::Duration> = None;
+        let now = std::time::Instant::now();
+        self.streams.retain(|_, stream| {
+            if stream.cancel.is_closed() {
+                return false;
+            }
+            let idle = idle_timeout.map_or(false, |timeout| {
+                !stream.tx.is_closed() && now.duration_since(stream.last_active) >= timeout
+            });
+            if idle {
+                let _ = stream
+                    .cancel
+                    .try_send(tonic::Status::cancelled("stream idle"));
+            }
+            !idle
+        });
+    }
+    pub fn close(self) {
+        self.shutdown.close();
+    }
+    pub async fn drain(mut self, timeout: std::time::Duration) -> bool {
+        for stream in self.streams.values() {
+            stream.tx.close();
+        }
+        self.tasks = None;
+        let drained = async_std::future::timeout(timeout, self.tasks_done.recv())
+            .await
+            .is_ok();
+        self.close();
+        drained
+    }
+}
+#[async_trait::async_trait]
+trait TremorGrpcClient: Send + std::fmt::Debug {
+    async fn send_unary_request(
+        &mut self,
+        _method: &str,
+        _event: &tremor_pipeline::Event,
+        _reply_tx: async_std::channel::Sender<tremor_script::EventPayload>,
+        _tasks: &TaskToken,
+        _in_flight: &InFlight,
+    ) -> Result<RequestStatus> {
+        Ok(RequestStatus::Sent)
+    }
+    async fn send_client_stream_request(
+        &mut self,
+        _method: &str,
+        _event: &tremor_pipeline::Event,
+        _reply_tx: async_std::channel::Sender<tremor_script::EventPayload>,
+        _tasks: &TaskToken,
+        _streams: &mut hashbrown::HashMap<StreamKey, StreamHandle>,
+    ) -> Result<RequestStatus> {
+        Ok(RequestStatus::Sent)
+    }
+    async fn send_server_stream_request(
+        &mut self,
+        _method: &str,
+        _event: &tremor_pipeline::Event,
+        _reply_tx: async_std::channel::Sender<tremor_script::EventPayload>,
+        _tasks: &TaskToken,
+        _in_flight: &InFlight,
+    ) -> Result<RequestStatus> {
+        Ok(RequestStatus::Sent)
+    }
+    async fn send_binary_stream_request(
+        &mut self,
+        _method: &str,
+        _event: &tremor_pipeline::Event,
+        _reply_tx: async_std::channel::Sender<tremor_script::EventPayload>,
+        _tasks: &TaskToken,
+        _streams: &mut hashbrown::HashMap<StreamKey, StreamHandle>,
+    ) -> Result<RequestStatus> {
+        Ok(RequestStatus::Sent)
+    }
+}
+#[async_trait::async_trait]
+impl TremorGrpcClient for inventory::items_client::ItemsClient<tonic::transport::Channel> {
+    async fn send_unary_request(
+        &mut self,
+        method: &str,
+        event: &tremor_pipeline::Event,
+        reply_tx: async_std::channel::Sender<tremor_script::EventPayload>,
+        tasks: &TaskToken,
+        in_flight: &InFlight,
+    ) -> Result<RequestStatus> {
+        match method {
+            "inventory.Items/Get" => {
+                let permits = match in_flight.try_acquire(event.value_meta_iter().count()) {
+                    Some(permits) => permits,
+                    None => return Ok(RequestStatus::Busy),
+                };
+                for ((value, meta), permit) in event.value_meta_iter().zip(permits) {
+                    let mut context = ReplyContext::new("inventory.Items/Get", &event.id, meta);
+                    let body: std::result::Result<inventory::ItemRequest, _> =
+                        tremor_value::structurize(value.clone_static());
+                    let body = match body {
+                        Ok(body) => body,
+                        Err(e) => {
+                            let status = tonic::Status::invalid_argument(e.to_string());
+                            reply_tx
+                                .send(context.error(&status))
+                                .await
+                                .map_err(into_error)?;
+                            continue;
+                        }
+                    };
+                    let mut request = tonic::Request::new(body);
+                    if let Err(status) = apply_headers(request.metadata_mut(), meta) {
+                        reply_tx
+                            .send(context.error(&status))
+                            .await
+                            .map_err(into_error)?;
+                        continue;
+                    }
+                    let deadline = apply_timeout(&mut request, meta, None);
+                    let mut client = self.clone();
+                    let reply_tx = reply_tx.clone();
+                    let tasks = tasks.clone();
+                    async_std::task::spawn(async move {
+                        let result: std::result::Result<
+                            tonic::Response<inventory::Item>,
+                            tonic::Status,
+                        > = cancellable(
+                            &tasks.shutdown,
+                            with_deadline(deadline, client.get(request)),
+                        )
+                        .await;
+                        let event = match result.and_then(|resp| {
+                            let headers = metadata_value(resp.metadata());
+                            let message = tremor_value::to_value(resp.into_inner())
+                                .map_err(|e| tonic::Status::internal(e.to_string()))?;
+                            Ok((message, headers))
+                        }) {
+                            Ok((message, headers)) => context.reply(message, headers),
+                            Err(status) => context.error(&status),
+                        };
+                        drop(permit);
+                        let _ = send_reply(&reply_tx, &tasks.shutdown, event).await;
+                    });
+                }
+                Ok(RequestStatus::Sent)
+            }
+            _ => Ok(RequestStatus::Sent),
+        }
+    }
+    async fn send_server_stream_request(
+        &mut self,
+        method: &str,
+        event: &tremor_pipeline::Event,
+        reply_tx: async_std::channel::Sender<tremor_script::EventPayload>,
+        tasks: &TaskToken,
+        in_flight: &InFlight,
+    ) -> Result<RequestStatus> {
+        match method {
+            "inventory.Items/List" => {
+                let permits = match in_flight.try_acquire(event.value_meta_iter().count()) {
+                    Some(permits) => permits,
+                    None => return Ok(RequestStatus::Busy),
+                };
+                for ((value, meta), permit) in event.value_meta_iter().zip(permits) {
+                    let mut context = ReplyContext::new("inventory.Items/List", &event.id, meta);
+                    let body: std::result::Result<inventory::ItemRequest, _> =
+                        tremor_value::structurize(value.clone_static());
+                    let body = match body {
+                        Ok(body) => body,
+                        Err(e) => {
+                            let status = tonic::Status::invalid_argument(e.to_string());
+                            reply_tx
+                                .send(context.error(&status))
+                                .await
+                                .map_err(into_error)?;
+                            continue;
+                        }
+                    };
+                    let mut request = tonic::Request::new(body);
+                    if let Err(status) = apply_headers(request.metadata_mut(), meta) {
+                        reply_tx
+                            .send(context.error(&status))
+                            .await
+                            .map_err(into_error)?;
+                        continue;
+                    }
+                    let deadline = apply_timeout(&mut request, meta, None);
+                    let mut client = self.clone();
+                    let reply_tx = reply_tx.clone();
+                    let tasks = tasks.clone();
+                    async_std::task::spawn(async move {
+                        let resp: tonic::Response<tonic::Streaming<inventory::Item>> =
+                            match cancellable(
+                                &tasks.shutdown,
+                                with_deadline(deadline, client.list(request)),
+                            )
+                            .await
+                            {
+                                Ok(resp) => resp,
+                                Err(status) => {
+                                    drop(permit);
+                                    let _ = send_reply(
+                                        &reply_tx,
+                                        &tasks.shutdown,
+                                        context.error(&status),
+                                    )
+                                    .await;
+                                    return;
+                                }
+                            };
+                        let headers = metadata_value(resp.metadata());
+                        let mut stream = resp.into_inner();
+                        let mut permit = Some(permit);
+                        loop {
+                            let (event, done) = match cancellable(
+                                &tasks.shutdown,
+                                with_deadline(deadline, stream.message()),
+                            )
+                            .await
+                            {
+                                Ok(Some(item)) => match tremor_value::to_value(item) {
+                                    Ok(message) => (context.reply(message, headers.clone()), false),
+                                    Err(e) => (
+                                        context.error(&tonic::Status::internal(e.to_string())),
+                                        true,
+                                    ),
+                                },
+                                Ok(None) => match cancellable(
+                                    &tasks.shutdown,
+                                    with_deadline(deadline, stream.trailers()),
+                                )
+                                .await
+                                {
+                                    Ok(trailers) => (context.end(trailers.as_ref()), true),
+                                    Err(status) => (context.error(&status), true),
+                                },
+                                Err(status) => (context.error(&status), true),
+                            };
+                            if done {
+                                permit.take();
+                            }
+                            if !send_reply(&reply_tx, &tasks.shutdown, event).await || done {
+                                break;
+                            }
+                        }
+                    });
+                }
+                Ok(RequestStatus::Sent)
+            }
+            _ => Ok(RequestStatus::Sent),
+        }
+    }
+}
@@ -0,0 +1,287 @@
+pub mod acme {
+    pub mod billing {
+        pub mod v1 {
+            tonic::include_proto!("acme.billing.v1");
+        }
+    }
+}
+use async_std::prelude::StreamExt;
+use value_trait::ValueAccess;
+#[derive(Debug)]
+pub enum Error {
+    Status(tonic::Status),
+    Transport(tonic::transport::Error),
+    Value(tremor_value::Error),
+    ChannelClosed,
+}
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Status(status) => write!(f, "gRPC call failed: {}", status),
+            Error::Transport(e) => write!(f, "gRPC transport error: {}", e),
+            Error::Value(e) => write!(f, "value conversion failed: {}", e),
+            Error::ChannelClosed => write!(f, "channel closed"),
+        }
+    }
+}
+impl std::error::Error for Error {}
+impl From<tonic::Status> for Error {
+    fn from(status: tonic::Status) -> Self {
+        Error::Status(status)
+    }
+}
+impl From<tonic::transport::Error> for Error {
+    fn from(e: tonic::transport::Error) -> Self {
+        Error::Transport(e)
+    }
+}
+impl From<tremor_value::Error> for Error {
+    fn from(e: tremor_value::Error) -> Self {
+        Error::Value(e)
+    }
+}
+impl<T> From<async_std::channel::SendError<T>> for Error {
+    fn from(_: async_std::channel::SendError<T>) -> Self {
+        Error::ChannelClosed
+    }
+}
+pub type Result<T> = std::result::Result<T, Error>;
+fn into_error<E>(e: E) -> Error
+where
+    Error: From<E>,
+{
+    Error::from(e)
+}
+struct StaticValue(tremor_value::Value<'static>);
+#[doc = r" Forwards every call to the pipeline as events with `request_path` and `request_id` meta"]
+#[doc = r" fields. Client streams send one event per message, followed by a null event with `flag`"]
+#[doc = r" set once the stream ends, other calls send a single event with `flag` set. If a client"]
+#[doc = r" stream fails, its last event also has the `grpc_status` and `grpc_message` of the error."]
+#[doc = r""]
+#[doc = r" Replies go back through `send_reply` with the `request_id` of the call. Unary and client"]
+#[doc = r" streaming calls answer with the first reply, streaming responses send every reply until"]
+#[doc = r" one has `flag` set. A null reply with `flag` set ends the call without a message. Replies"]
+#[doc = r" to calls that have ended are ignored."]
+#[derive(Debug, Clone)]
+pub struct GrpcServerHandler {
+    request_tx: async_std::channel::Sender<tremor_script::EventPayload>,
+    pending: std::sync::Arc<std::sync::Mutex<PendingMap>>,
+    next_request_id: std::sync::Arc<std::sync::atomic::AtomicU64>,
+}
+type PendingMap = hashbrown::HashMap<u64, async_std::channel::Sender<StaticValue>>;
+struct PendingGuard {
+    pending: std::sync::Arc<std::sync::Mutex<PendingMap>>,
+    request_id: u64,
+}
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        lock_pending(&self.pending).remove(&self.request_id);
+    }
+}
+fn lock_pending(pending: &std::sync::Mutex<PendingMap>) -> std::sync::MutexGuard<'_, PendingMap> {
+    pending
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+impl GrpcServerHandler {
+    pub fn new(request_tx: async_std::channel::Sender<tremor_script::EventPayload>) -> Self {
+        GrpcServerHandler {
+            request_tx,
+            pending: std::sync::Arc::new(std::sync::Mutex::new(hashbrown::HashMap::new())),
+            next_request_id: std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0)),
+        }
+    }
+    pub async fn serve(self, addr: std::net::SocketAddr) -> Result<()> {
+        tonic::transport::Server::builder()
+            .add_service(acme::billing::v1::invoices_server::InvoicesServer::new(
+                self.clone(),
+            ))
+            .serve(addr)
+            .await
+            .map_err(into_error)?;
+        Ok(())
+    }
+    pub async fn send_reply(&self, event: tremor_pipeline::Event) -> Result<()> {
+        for (value, meta) in event.value_meta_iter() {
+            if let Some(request_id) = meta.get_u64("request_id") {
+                let flag = meta.contains_key("flag");
+                let tx = if flag {
+                    lock_pending(&self.pending).remove(&request_id)
+                } else {
+                    lock_pending(&self.pending).get(&request_id).cloned()
+                };
+                if let Some(tx) = tx {
+                    let is_null = matches!(
+                        value,
+                        tremor_value::Value::Static(value_trait::StaticNode::Null)
+                    );
+                    if !(flag && is_null) {
+                        tx.send(StaticValue(value.clone_static()))
+                            .await
+                            .map_err(into_error)?;
+                    }
+                    if flag {
+                        tx.close();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    fn register(&self) -> (PendingGuard, async_std::channel::Receiver<StaticValue>) {
+        let request_id = self
+            .next_request_id
+            .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let (tx, rx) = async_std::channel::unbounded::<StaticValue>();
+        lock_pending(&self.pending).insert(request_id, tx);
+        let guard = PendingGuard {
+            pending: self.pending.clone(),
+            request_id,
+        };
+        (guard, rx)
+    }
+    async fn forward(
+        &self,
+        request_path: &'static str,
+        request_id: u64,
+        value: tremor_value::Value<'static>,
+        flag: bool,
+        error: Option<&tonic::Status>,
+    ) -> std::result::Result<(), tonic::Status> {
+        let mut meta = tremor_value::value::Object::with_capacity(5);
+        meta.insert("request_path".into(), request_path.into());
+        meta.insert("request_id".into(), request_id.into());
+        if flag {
+            meta.insert("flag".into(), true.into());
+        }
+        if let Some(status) = error {
+            meta.insert("grpc_status".into(), (status.code() as i32).into());
+            meta.insert("grpc_message".into(), status.message().to_string().into());
+        }
+        let event: tremor_script::EventPayload = (value, meta).into();
+        self.request_tx.send(event).await.map_err(status_from)
+    }
+    async fn forward_stream<T: serde::Serialize>(
+        &self,
+        request_path: &'static str,
+        request_id: u64,
+        stream: &mut tonic::Streaming<T>,
+    ) -> std::result::Result<(), tonic::Status> {
+        let null = tremor_value::Value::Static(value_trait::StaticNode::Null);
+        let status = loop {
+            let item = match stream.message().await {
+                Ok(Some(item)) => item,
+                Ok(None) => {
+                    return self
+                        .forward(request_path, request_id, null, true, None)
+                        .await
+                }
+                Err(status) => break status,
+            };
+            match tremor_value::to_value(item) {
+                Ok(value) => {
+                    self.forward(request_path, request_id, value, false, None)
+                        .await?
+                }
+                Err(e) => break status_from(e),
+            }
+        };
+        self.forward(request_path, request_id, null, true, Some(&status))
+            .await?;
+        Err(status)
+    }
+}
+fn status_from<E: std::fmt::Display>(e: E) -> tonic::Status {
+    tonic::Status::internal(e.to_string())
+}
+#[tonic::async_trait]
+impl acme::billing::v1::invoices_server::Invoices for GrpcServerHandler {
+    async fn get(
+        &self,
+        request: tonic::Request<acme::billing::v1::GetInvoiceRequest>,
+    ) -> std::result::Result<tonic::Response<acme::billing::v1::Invoice>, tonic::Status> {
+        let (guard, mut rx) = self.register();
+        let request_id = guard.request_id;
+        let value = tremor_value::to_value(request.into_inner()).map_err(status_from)?;
+        self.forward(
+            "acme.billing.v1.Invoices/Get",
+            request_id,
+            value,
+            true,
+            None,
+        )
+        .await?;
+        let reply = rx.next().await;
+        drop(guard);
+        let reply = reply.ok_or_else(|| tonic::Status::unavailable("no reply from pipeline"))?;
+        let body: acme::billing::v1::Invoice =
+            tremor_value::structurize(reply.0).map_err(status_from)?;
+        Ok(tonic::Response::new(body))
+    }
+    type WatchStream = std::pin::Pin<
+        Box<
+            dyn async_std::stream::Stream<
+                    Item = std::result::Result<acme::billing::v1::Invoice, tonic::Status>,
+                > + Send
+                + Sync
+                + 'static,
+        >,
+    >;
+    async fn watch(
+        &self,
+        request: tonic::Request<acme::billing::v1::GetInvoiceRequest>,
+    ) -> std::result::Result<tonic::Response<Self::WatchStream>, tonic::Status> {
+        let (guard, rx) = self.register();
+        let request_id = guard.request_id;
+        let value = tremor_value::to_value(request.into_inner()).map_err(status_from)?;
+        self.forward(
+            "acme.billing.v1.Invoices/Watch",
+            request_id,
+            value,
+            true,
+            None,
+        )
+        .await?;
+        let stream = rx.map(move |reply| {
+            let _guard = &guard;
+            let body: std::result::Result<acme::billing::v1::Invoice, tonic::Status> =
+                tremor_value::structurize(reply.0).map_err(status_from);
+            body
+        });
+        Ok(tonic::Response::new(Box::pin(stream)))
+    }
+    type SyncLinesStream = std::pin::Pin<
+        Box<
+            dyn async_std::stream::Stream<
+                    Item = std::result::Result<acme::billing::v1::invoice::Line, tonic::Status>,
+                > + Send
+                + Sync
+                + 'static,
+        >,
+    >;
+    async fn sync_lines(
+        &self,
+        request: tonic::Request<tonic::Streaming<acme::billing::v1::invoice::Line>>,
+    ) -> std::result::Result<tonic::Response<Self::SyncLinesStream>, tonic::Status> {
+        let (guard, rx) = self.register();
+        let request_id = guard.request_id;
+        let mut stream = request.into_inner();
+        let handler = self.clone();
+        async_std::task::spawn(async move {
+            let _ = handler
+                .forward_stream(
+                    "acme.billing.v1.Invoices/SyncLines",
+                    request_id,
+                    &mut stream,
+                )
+                .await;
+        });
+        let stream = rx.map(move |reply| {
+            let _guard = &guard;
+            let body: std::result::Result<acme::billing::v1::invoice::Line, tonic::Status> =
+                tremor_value::structurize(reply.0).map_err(status_from);
+            body
+        });
+        Ok(tonic::Response::new(Box::pin(stream)))
+    }
+}